@@ -142,3 +142,72 @@ fn bounded_100_tx(b: &mut Bencher) {
         }
     })
 }
+
+/// Single producer, single consumer, one message at a time. Baseline for
+/// the batched benchmarks below.
+#[bench]
+fn bounded_unbatched_1000(b: &mut Bencher) {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    b.iter(|| {
+        let (mut tx, mut rx) = mpsc::channel(1000);
+
+        for i in 0..1000 {
+            tx.try_send(i).unwrap();
+        }
+        for i in 0..1000 {
+            assert_eq!(Poll::Ready(Some(i)), rx.poll_next_unpin(&mut cx));
+        }
+    })
+}
+
+/// Single producer, single consumer, sending and receiving in batches of 100
+#[bench]
+fn bounded_batched_1000(b: &mut Bencher) {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut buf = Vec::with_capacity(100);
+    b.iter(|| {
+        let (mut tx, mut rx) = mpsc::channel(1000);
+
+        for i in 0..10 {
+            tx.try_send_batch((i * 100..(i + 1) * 100).collect()).unwrap();
+        }
+        for _ in 0..10 {
+            buf.clear();
+            assert_eq!(Poll::Ready(100), rx.poll_recv_many(&mut cx, &mut buf, 100));
+        }
+    })
+}
+
+/// Unbounded, single producer, single consumer, one message at a time
+#[bench]
+fn unbounded_unbatched_1000(b: &mut Bencher) {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    b.iter(|| {
+        let (tx, mut rx) = mpsc::unbounded();
+
+        for i in 0..1000 {
+            tx.unbounded_send(i).unwrap();
+        }
+        for i in 0..1000 {
+            assert_eq!(Poll::Ready(Some(i)), rx.poll_next_unpin(&mut cx));
+        }
+    })
+}
+
+/// Unbounded, single producer, single consumer, in batches of 100
+#[bench]
+fn unbounded_batched_1000(b: &mut Bencher) {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut buf = Vec::with_capacity(100);
+    b.iter(|| {
+        let (tx, mut rx) = mpsc::unbounded();
+
+        for i in 0..10 {
+            tx.unbounded_send_batch((i * 100..(i + 1) * 100).collect()).unwrap();
+        }
+        for _ in 0..10 {
+            buf.clear();
+            assert_eq!(Poll::Ready(100), rx.poll_recv_many(&mut cx, &mut buf, 100));
+        }
+    })
+}
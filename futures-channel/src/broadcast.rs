@@ -0,0 +1,354 @@
+//! A multi-producer, multi-consumer queue where every value sent is received
+//! by every receiver.
+//!
+//! Channel creation provides a [`Sender`](Sender) and a first
+//! [`Receiver`](Receiver). More receivers are created with
+//! [`Sender::subscribe`](Sender::subscribe); each one sees every value sent
+//! after it was created. [`Receiver`](Receiver) implements
+//! [`Stream`](futures_core::stream::Stream).
+//!
+//! The channel keeps the last `capacity` values in a ring buffer. Sending
+//! never waits for the receivers: once the buffer is full, each new value
+//! overwrites the oldest one. A receiver which falls behind by more than
+//! `capacity` values misses the overwritten ones, and is told how many it
+//! missed through a [`Lagged`](RecvError::Lagged) error before carrying on
+//! with the oldest value still in the buffer.
+//!
+//! # Disconnection
+//!
+//! When all [`Sender`](Sender) handles have been dropped, receivers still
+//! get the values remaining in the buffer, after which their stream ends.
+//! When all [`Receiver`](Receiver) handles have been dropped, sending fails.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::channel::broadcast;
+//! use futures::executor::block_on_stream;
+//!
+//! let (tx, rx1) = broadcast::channel(16);
+//! let rx2 = tx.subscribe();
+//!
+//! tx.send(1).unwrap();
+//! tx.send(2).unwrap();
+//! drop(tx);
+//!
+//! let got1: Vec<_> = block_on_stream(rx1).map(Result::unwrap).collect();
+//! let got2: Vec<_> = block_on_stream(rx2).map(Result::unwrap).collect();
+//! assert_eq!(got1, vec![1, 2]);
+//! assert_eq!(got2, vec![1, 2]);
+//! ```
+
+use futures_core::stream::{FusedStream, Stream};
+use futures_core::task::{Context, Poll, Waker};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The sending half of a broadcast channel.
+///
+/// This is created by the [`channel`](channel) function, and can be cloned
+/// to get more senders.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of a broadcast channel.
+///
+/// This is created by the [`channel`](channel) function or by
+/// [`Sender::subscribe`](Sender::subscribe).
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // Identifies this receiver's waker in `State::wakers`
+    id: usize,
+    // Position of the next value to receive
+    next: u64,
+    terminated: bool,
+}
+
+// The channel does not ever project Pin to the inner T
+impl<T> Unpin for Sender<T> {}
+impl<T> Unpin for Receiver<T> {}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    // The last `capacity` values sent
+    buffer: VecDeque<T>,
+    // Position of the front of `buffer`, i.e. the number of values that have
+    // been overwritten so far
+    head: u64,
+    num_senders: usize,
+    num_receivers: usize,
+    // Receivers waiting for a value, by id
+    wakers: HashMap<usize, Waker>,
+    next_id: usize,
+}
+
+/// The error returned by [`Sender::send`](Sender::send) when there are no
+/// receivers left. Contains the value that failed to be sent.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// The error yielded by a [`Receiver`](Receiver) stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// The receiver fell behind, and this many values were overwritten
+    /// before it could receive them. The next value received is the oldest
+    /// one still in the channel.
+    Lagged(u64),
+}
+
+/// The error returned by [`Receiver::try_recv`](Receiver::try_recv).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There is no new value in the channel right now.
+    Empty,
+    /// The receiver fell behind, and this many values were overwritten
+    /// before it could receive them.
+    Lagged(u64),
+    /// All senders have been dropped and every value has been received.
+    Closed,
+}
+
+/// Creates a new broadcast channel which keeps the last `capacity` values
+/// sent, returning the sender and a first receiver.
+///
+/// # Panics
+///
+/// Panics if `capacity == 0`.
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "broadcast channel capacity must be at least 1");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buffer: VecDeque::with_capacity(capacity),
+            head: 0,
+            num_senders: 1,
+            num_receivers: 0,
+            wakers: HashMap::new(),
+            next_id: 0,
+        }),
+        capacity,
+    });
+    let rx = Receiver::new(shared.clone());
+    (Sender { shared }, rx)
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl<T> State<T> {
+    // Position of the next value to be sent
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+
+    fn wake_all(&mut self) {
+        for (_, waker) in self.wakers.drain() {
+            waker.wake();
+        }
+    }
+
+    // Receive the value at position `next`, advancing it.
+    fn recv(&self, next: &mut u64) -> Result<T, TryRecvError>
+        where T: Clone
+    {
+        if *next < self.head {
+            let missed = self.head - *next;
+            *next = self.head;
+            return Err(TryRecvError::Lagged(missed));
+        }
+        match self.buffer.get((*next - self.head) as usize) {
+            Some(msg) => {
+                *next += 1;
+                Ok(msg.clone())
+            }
+            None if self.num_senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to every receiver currently subscribed.
+    ///
+    /// This never waits: if the channel is full, the oldest value is
+    /// overwritten. Returns an error containing the value if there are no
+    /// receivers.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let overwritten = {
+            let mut state = self.shared.lock();
+            if state.num_receivers == 0 {
+                return Err(SendError(msg));
+            }
+            let overwritten = if state.buffer.len() == self.shared.capacity {
+                state.head += 1;
+                state.buffer.pop_front()
+            } else {
+                None
+            };
+            state.buffer.push_back(msg);
+            state.wake_all();
+            overwritten
+        };
+        // Drop the message outside of the lock
+        drop(overwritten);
+        Ok(())
+    }
+
+    /// Creates a new receiver, which will receive every value sent from now
+    /// on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver::new(self.shared.clone())
+    }
+
+    /// Returns the number of receivers currently subscribed.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().num_receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.lock().num_senders += 1;
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.num_senders -= 1;
+        if state.num_senders == 0 {
+            // let the receivers observe the end of the stream
+            state.wake_all();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    fn new(shared: Arc<Shared<T>>) -> Receiver<T> {
+        let (id, next) = {
+            let mut state = shared.lock();
+            state.num_receivers += 1;
+            state.next_id += 1;
+            (state.next_id, state.tail())
+        };
+        Receiver { shared, id, next, terminated: false }
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Attempts to receive the next value outside of the context of a task.
+    ///
+    /// Does not schedule a task wakeup or have any other side effects.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.shared.lock().recv(&mut self.next)
+    }
+}
+
+impl<T: Clone> Stream for Receiver<T> {
+    type Item = Result<T, RecvError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let this = &mut *self;
+        let mut state = this.shared.lock();
+        match state.recv(&mut this.next) {
+            Ok(msg) => Poll::Ready(Some(Ok(msg))),
+            Err(TryRecvError::Lagged(missed)) => Poll::Ready(Some(Err(RecvError::Lagged(missed)))),
+            Err(TryRecvError::Closed) => {
+                this.terminated = true;
+                Poll::Ready(None)
+            }
+            Err(TryRecvError::Empty) => {
+                state.wakers.insert(this.id, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T: Clone> FusedStream for Receiver<T> {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.num_receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("SendError")
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "send failed because there are no receivers")
+    }
+}
+
+impl<T> Error for SendError<T> {
+    fn description(&self) -> &str {
+        "send failed because there are no receivers"
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RecvError::Lagged(missed) => write!(fmt, "receiver lagged behind by {} values", missed),
+        }
+    }
+}
+
+impl Error for RecvError {
+    fn description(&self) -> &str {
+        "receiver lagged behind"
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TryRecvError::Empty => write!(fmt, "channel is empty"),
+            TryRecvError::Lagged(missed) => write!(fmt, "receiver lagged behind by {} values", missed),
+            TryRecvError::Closed => write!(fmt, "channel is closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {
+    fn description(&self) -> &str {
+        match *self {
+            TryRecvError::Empty => "channel is empty",
+            TryRecvError::Lagged(_) => "receiver lagged behind",
+            TryRecvError::Closed => "channel is closed",
+        }
+    }
+}
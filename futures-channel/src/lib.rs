@@ -14,6 +14,16 @@
 #[cfg(feature = "std")]
 mod lock;
 #[cfg(feature = "std")]
+pub mod broadcast;
+#[cfg(feature = "std")]
+pub mod lossy;
+#[cfg(feature = "std")]
+pub mod mpmc;
+#[cfg(feature = "std")]
 pub mod mpsc;
 #[cfg(feature = "std")]
 pub mod oneshot;
+#[cfg(feature = "std")]
+pub mod priority;
+#[cfg(feature = "std")]
+pub mod watch;
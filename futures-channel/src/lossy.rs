@@ -0,0 +1,295 @@
+//! A bounded multi-producer, single-consumer queue which drops messages
+//! instead of applying backpressure.
+//!
+//! This is useful for data such as telemetry samples, where losing some
+//! messages is preferable to blocking the producers or buffering without
+//! bound. Channel creation provides a [`Sender`](Sender) and a
+//! [`Receiver`](Receiver). The channel keeps at most `capacity` messages in a
+//! ring buffer, and the [`Overflow`](Overflow) policy decides which message
+//! is dropped when a message is sent while the buffer is full: either the
+//! oldest one in the buffer, or the new one. Either way,
+//! [`try_send`](Sender::try_send) never fails for lack of capacity, and the
+//! receiver can tell how many messages were lost through
+//! [`dropped_count`](Receiver::dropped_count).
+//!
+//! [`Receiver`](Receiver) implements [`Stream`](futures_core::stream::Stream)
+//! just like [`mpsc::Receiver`](crate::mpsc::Receiver).
+//!
+//! # Disconnection
+//!
+//! When all [`Sender`](Sender) handles have been dropped, the receiver still
+//! gets the messages remaining in the buffer, after which its stream ends.
+//! When the [`Receiver`](Receiver) has been dropped or
+//! [`close`](Receiver::close)d, sending fails.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::channel::lossy::{self, Overflow};
+//! use futures::executor::block_on_stream;
+//!
+//! let (tx, rx) = lossy::channel(2, Overflow::DropOldest);
+//! for i in 0..5 {
+//!     tx.try_send(i).unwrap();
+//! }
+//! drop(tx);
+//!
+//! let mut rx = block_on_stream(rx);
+//! assert_eq!(rx.next(), Some(3));
+//! assert_eq!(rx.next(), Some(4));
+//! assert_eq!(rx.next(), None);
+//! assert_eq!(rx.into_inner().dropped_count(), 3);
+//! ```
+
+use futures_core::stream::{FusedStream, Stream};
+use futures_core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub use crate::mpsc::TryRecvError;
+
+/// Which message to drop when a message is sent into a full channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the oldest message in the channel to make room for the new one.
+    DropOldest,
+    /// Drop the message being sent, keeping the ones already in the channel.
+    DropNewest,
+}
+
+/// The transmission end of a lossy channel.
+///
+/// This is created by the [`channel`](channel) function, and can be cloned
+/// to get more senders.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving end of a lossy channel.
+///
+/// This is created by the [`channel`](channel) function.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    terminated: bool,
+}
+
+// The channel does not ever project Pin to the inner T
+impl<T> Unpin for Sender<T> {}
+impl<T> Unpin for Receiver<T> {}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    buffer: VecDeque<T>,
+    // Cleared when the receiver is dropped or closed
+    open: bool,
+    num_senders: usize,
+    // Number of messages dropped so far because the buffer was full
+    dropped: u64,
+    recv_task: Option<Waker>,
+}
+
+/// The error returned by [`Sender::try_send`](Sender::try_send) when the
+/// receiver is gone. Contains the message that failed to be sent.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// Creates a lossy channel which holds at most `capacity` messages, dropping
+/// messages according to `overflow` when it is full.
+///
+/// # Panics
+///
+/// Panics if `capacity == 0`.
+pub fn channel<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "lossy channel capacity must be at least 1");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buffer: VecDeque::with_capacity(capacity),
+            open: true,
+            num_senders: 1,
+            dropped: 0,
+            recv_task: None,
+        }),
+        capacity,
+        overflow,
+    });
+    let rx = Receiver { shared: shared.clone(), terminated: false };
+    (Sender { shared }, rx)
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl<T> State<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(task) = self.recv_task.take() {
+            task.wake();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a message, dropping a message if the channel is full.
+    ///
+    /// This never fails for lack of capacity. Returns an error containing
+    /// the message if the receiver has been dropped or closed.
+    pub fn try_send(&self, msg: T) -> Result<(), SendError<T>> {
+        let dropped = {
+            let mut state = self.shared.lock();
+            if !state.open {
+                return Err(SendError(msg));
+            }
+            let dropped = if state.buffer.len() < self.shared.capacity {
+                state.buffer.push_back(msg);
+                None
+            } else {
+                state.dropped += 1;
+                match self.shared.overflow {
+                    Overflow::DropOldest => {
+                        let oldest = state.buffer.pop_front();
+                        state.buffer.push_back(msg);
+                        oldest
+                    }
+                    Overflow::DropNewest => Some(msg),
+                }
+            };
+            state.wake_receiver();
+            dropped
+        };
+        // Drop the message outside of the lock
+        drop(dropped);
+        Ok(())
+    }
+
+    /// Returns whether the receiver has been dropped or closed.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().open
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.lock().num_senders += 1;
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.num_senders -= 1;
+        if state.num_senders == 0 {
+            // let the receiver observe the end of the stream
+            state.wake_receiver();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Closes the receiving half of the channel, without dropping it.
+    ///
+    /// This prevents any further messages from being sent on the channel
+    /// while still enabling the receiver to drain messages that are
+    /// buffered.
+    pub fn close(&mut self) {
+        self.shared.lock().open = false;
+    }
+
+    /// Returns the total number of messages that have been dropped because
+    /// the channel was full.
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Tries to receive the next message without notifying a context if empty.
+    ///
+    /// It is not recommended to call this function from inside of a future,
+    /// only when you've otherwise arranged to be notified when the channel is
+    /// no longer empty.
+    pub fn try_next(&mut self) -> Result<Option<T>, TryRecvError> {
+        match self.next_message(None) {
+            Poll::Ready(msg) => Ok(msg),
+            Poll::Pending => Err(TryRecvError { _inner: () }),
+        }
+    }
+
+    fn next_message(&mut self, cx: Option<&mut Context<'_>>) -> Poll<Option<T>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let mut state = self.shared.lock();
+        if let Some(msg) = state.buffer.pop_front() {
+            Poll::Ready(Some(msg))
+        } else if !state.open || state.num_senders == 0 {
+            self.terminated = true;
+            Poll::Ready(None)
+        } else {
+            if let Some(cx) = cx {
+                state.recv_task = Some(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<T>> {
+        self.next_message(Some(cx))
+    }
+}
+
+impl<T> FusedStream for Receiver<T> {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let remaining = {
+            let mut state = self.shared.lock();
+            state.open = false;
+            state.buffer.split_off(0)
+        };
+        // Drop the buffered messages outside of the lock
+        drop(remaining);
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("SendError")
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "send failed because receiver is gone")
+    }
+}
+
+impl<T> Error for SendError<T> {
+    fn description(&self) -> &str {
+        "send failed because receiver is gone"
+    }
+}
@@ -0,0 +1,450 @@
+//! A multi-producer, multi-consumer queue where each value sent is received
+//! by exactly one receiver.
+//!
+//! This is useful for distributing work among a set of worker tasks. Channel
+//! creation provides a [`Sender`](Sender) and a [`Receiver`](Receiver), both
+//! of which can be cloned. [`Receiver`](Receiver) implements
+//! [`Stream`](futures_core::stream::Stream); whichever receiver polls first
+//! gets the next value.
+//!
+//! The channel is bounded, with the same backpressure as
+//! [`mpsc::channel`](crate::mpsc::channel): its capacity is `buffer` plus the
+//! number of senders. Each sender gets one guaranteed slot, and once the
+//! buffer is full a sender which sent a value has to wait until a value has
+//! been received before it can send again.
+//!
+//! # Disconnection
+//!
+//! When all [`Sender`](Sender) handles have been dropped, receivers still
+//! get the values remaining in the channel, after which their streams end.
+//!
+//! The channel is closed when all [`Receiver`](Receiver) handles have been
+//! dropped, or when any of them calls [`close`](Receiver::close). After that,
+//! all further attempts to send result in an error, while the remaining
+//! receivers can still drain the values already in the channel.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::channel::mpmc;
+//! use futures::executor::block_on_stream;
+//!
+//! let (mut tx, rx1) = mpmc::channel(4);
+//! let rx2 = rx1.clone();
+//!
+//! for i in 0..4 {
+//!     tx.try_send(i).unwrap();
+//! }
+//! drop(tx);
+//!
+//! let mut got: Vec<_> = block_on_stream(rx1).take(2).collect();
+//! got.extend(block_on_stream(rx2));
+//! got.sort();
+//! assert_eq!(got, vec![0, 1, 2, 3]);
+//! ```
+
+use futures_core::stream::{FusedStream, Stream};
+use futures_core::task::{Context, Poll, Waker};
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::mpsc::SendErrorKind;
+pub use crate::mpsc::{SendError, TryRecvError, TrySendError};
+
+/// The transmission end of a multi-consumer channel.
+///
+/// This is created by the [`channel`](channel) function, and can be cloned
+/// to get more senders.
+#[derive(Debug)]
+pub struct Sender<T>(Option<SenderInner<T>>);
+
+/// The receiving end of a multi-consumer channel.
+///
+/// This is created by the [`channel`](channel) function, and can be cloned
+/// to get more receivers. Each value is received by only one of them.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // Identifies this receiver's waker in `State::recv_wakers`
+    id: usize,
+    terminated: bool,
+}
+
+// The channel does not ever project Pin to the inner T
+impl<T> Unpin for Sender<T> {}
+impl<T> Unpin for Receiver<T> {}
+
+#[derive(Debug)]
+struct SenderInner<T> {
+    shared: Arc<Shared<T>>,
+
+    // Handle to the task that is blocked on this sender. This handle is sent
+    // to the receivers via the parked queue.
+    sender_task: Arc<Mutex<SenderTask>>,
+
+    // True if the sender might be blocked. This is an optimization to avoid
+    // having to lock the mutex most of the time.
+    maybe_parked: bool,
+}
+
+#[derive(Debug)]
+struct SenderTask {
+    task: Option<Waker>,
+    is_parked: bool,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    buffer: usize,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    queue: VecDeque<T>,
+    // Cleared when the channel is closed by a receiver, or from the sending
+    // side by `close_channel`
+    open: bool,
+    num_senders: usize,
+    num_receivers: usize,
+    // Senders which sent a value beyond the buffer, in the order they did so.
+    // One of them is unparked for each value received.
+    parked: VecDeque<Arc<Mutex<SenderTask>>>,
+    // Receivers waiting for a value, by id
+    recv_wakers: HashMap<usize, Waker>,
+    next_id: usize,
+}
+
+/// Creates a bounded multi-consumer channel for distributing values among
+/// asynchronous tasks.
+///
+/// As with [`mpsc::channel`](crate::mpsc::channel), the channel's capacity is
+/// equal to `buffer + num-senders`.
+///
+/// The [`Receiver`](Receiver) returned implements the
+/// [`Stream`](futures_core::stream::Stream) trait, while [`Sender`](Sender)
+/// implements `Sink`.
+pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            open: true,
+            num_senders: 1,
+            num_receivers: 0,
+            parked: VecDeque::new(),
+            recv_wakers: HashMap::new(),
+            next_id: 0,
+        }),
+        buffer,
+    });
+    let rx = Receiver::new(shared.clone());
+    let tx = SenderInner {
+        shared,
+        sender_task: Arc::new(Mutex::new(SenderTask::new())),
+        maybe_parked: false,
+    };
+    (Sender(Some(tx)), rx)
+}
+
+impl SenderTask {
+    fn new() -> Self {
+        SenderTask {
+            task: None,
+            is_parked: false,
+        }
+    }
+
+    fn notify(&mut self) {
+        self.is_parked = false;
+
+        if let Some(task) = self.task.take() {
+            task.wake();
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl<T> State<T> {
+    fn wake_receivers(&mut self) {
+        for (_, waker) in self.recv_wakers.drain() {
+            waker.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+
+        // Wake up the parked senders as they'll see that the channel is
+        // closed, and the receivers waiting for a value that will never come.
+        while let Some(task) = self.parked.pop_front() {
+            task.lock().unwrap().notify();
+        }
+        self.wake_receivers();
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.queue.is_empty() && (!self.open || self.num_senders == 0)
+    }
+}
+
+fn disconnected() -> SendError {
+    SendError {
+        kind: SendErrorKind::Disconnected,
+    }
+}
+
+impl<T> SenderInner<T> {
+    fn try_send(&mut self, msg: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.shared.lock();
+        if !state.open {
+            return Err(TrySendError {
+                err: disconnected(),
+                val: msg,
+            });
+        }
+
+        // If the sender is currently blocked, reject the message
+        if self.maybe_parked && self.sender_task.lock().unwrap().is_parked {
+            return Err(TrySendError {
+                err: SendError {
+                    kind: SendErrorKind::Full,
+                },
+                val: msg,
+            });
+        }
+        self.maybe_parked = false;
+
+        state.queue.push_back(msg);
+        if state.queue.len() > self.shared.buffer {
+            // The buffer is full, so this sender has used its guaranteed slot
+            // and must wait for a value to be received before sending again.
+            {
+                let mut task = self.sender_task.lock().unwrap();
+                task.task = None;
+                task.is_parked = true;
+            }
+            state.parked.push_back(self.sender_task.clone());
+            self.maybe_parked = true;
+        }
+
+        // Any of the waiting receivers may take the value, and a woken
+        // receiver may no longer be polled, so wake them all.
+        state.wake_receivers();
+        Ok(())
+    }
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
+        let state = self.shared.lock();
+        if !state.open {
+            return Poll::Ready(Err(disconnected()));
+        }
+        if self.maybe_parked {
+            let mut task = self.sender_task.lock().unwrap();
+            if task.is_parked {
+                // Update the task in case the `Sender` has been moved to
+                // another task
+                task.task = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            self.maybe_parked = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Clone for SenderInner<T> {
+    fn clone(&self) -> SenderInner<T> {
+        self.shared.lock().num_senders += 1;
+        SenderInner {
+            shared: self.shared.clone(),
+            sender_task: Arc::new(Mutex::new(SenderTask::new())),
+            maybe_parked: false,
+        }
+    }
+}
+
+impl<T> Drop for SenderInner<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.num_senders -= 1;
+        if state.num_senders == 0 {
+            // let the receivers observe the end of the stream
+            state.wake_receivers();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Attempts to send a message on this `Sender`, returning the message
+    /// if there was an error.
+    pub fn try_send(&mut self, msg: T) -> Result<(), TrySendError<T>> {
+        if let Some(inner) = &mut self.0 {
+            inner.try_send(msg)
+        } else {
+            Err(TrySendError {
+                err: disconnected(),
+                val: msg,
+            })
+        }
+    }
+
+    /// Send a message on the channel.
+    ///
+    /// This function should only be called after
+    /// [`poll_ready`](Sender::poll_ready) has reported that the channel is
+    /// ready to receive a message.
+    pub fn start_send(&mut self, msg: T) -> Result<(), SendError> {
+        self.try_send(msg)
+            .map_err(|e| e.err)
+    }
+
+    /// Polls the channel to determine if there is guaranteed capacity to send
+    /// at least one item without waiting.
+    ///
+    /// # Return value
+    ///
+    /// This method returns:
+    ///
+    /// - `Ok(Poll::Ready(_))` if there is sufficient capacity;
+    /// - `Ok(Poll::Pending)` if the channel may not have
+    ///   capacity, in which case the current task is queued to be notified once
+    ///   capacity is available;
+    /// - `Err(SendError)` if the channel has been closed.
+    pub fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), SendError>> {
+        let inner = self.0.as_mut().ok_or_else(disconnected)?;
+        inner.poll_ready(cx)
+    }
+
+    /// Returns whether this channel is closed without needing a context.
+    pub fn is_closed(&self) -> bool {
+        self.0.as_ref().map(|inner| !inner.shared.lock().open).unwrap_or(true)
+    }
+
+    /// Closes this channel from the sender side, preventing any new messages.
+    pub fn close_channel(&mut self) {
+        if let Some(inner) = &self.0 {
+            inner.shared.lock().close();
+        }
+    }
+
+    /// Disconnects this sender from the channel, ending the receivers'
+    /// streams if there are no more senders left.
+    pub fn disconnect(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        Sender(self.0.clone())
+    }
+}
+
+impl<T> Receiver<T> {
+    fn new(shared: Arc<Shared<T>>) -> Receiver<T> {
+        let id = {
+            let mut state = shared.lock();
+            state.num_receivers += 1;
+            state.next_id += 1;
+            state.next_id
+        };
+        Receiver { shared, id, terminated: false }
+    }
+
+    /// Closes the channel for all receivers, without dropping this one.
+    ///
+    /// This prevents any further messages from being sent on the channel
+    /// while still enabling the receivers to drain messages that are
+    /// buffered.
+    pub fn close(&mut self) {
+        self.shared.lock().close();
+    }
+
+    /// Tries to receive the next message without notifying a context if empty.
+    ///
+    /// It is not recommended to call this function from inside of a future,
+    /// only when you've otherwise arranged to be notified when the channel is
+    /// no longer empty.
+    pub fn try_next(&mut self) -> Result<Option<T>, TryRecvError> {
+        match self.next_message(None) {
+            Poll::Ready(msg) => Ok(msg),
+            Poll::Pending => Err(TryRecvError { _inner: () }),
+        }
+    }
+
+    fn next_message(&mut self, cx: Option<&mut Context<'_>>) -> Poll<Option<T>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let mut state = self.shared.lock();
+        if let Some(msg) = state.queue.pop_front() {
+            // A slot was freed, so let the sender that has been waiting the
+            // longest send again.
+            if let Some(task) = state.parked.pop_front() {
+                task.lock().unwrap().notify();
+            }
+            Poll::Ready(Some(msg))
+        } else if state.is_terminated() {
+            state.recv_wakers.remove(&self.id);
+            self.terminated = true;
+            Poll::Ready(None)
+        } else {
+            if let Some(cx) = cx {
+                state.recv_wakers.insert(self.id, cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        let mut rx = Receiver::new(self.shared.clone());
+        rx.terminated = self.terminated;
+        rx
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<T>> {
+        self.next_message(Some(cx))
+    }
+}
+
+impl<T> FusedStream for Receiver<T> {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let remaining = {
+            let mut state = self.shared.lock();
+            state.num_receivers -= 1;
+            state.recv_wakers.remove(&self.id);
+            if state.num_receivers != 0 {
+                return;
+            }
+            state.close();
+            state.queue.split_off(0)
+        };
+        // Nobody is left to receive the buffered messages. Drop them outside
+        // of the lock, as they might hold senders of this very channel.
+        drop(remaining);
+    }
+}
@@ -0,0 +1,101 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use std::pin::Pin;
+
+use super::{Receiver, SendError, Sender};
+
+/// Future for the [`send_batch`](Sender::send_batch) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct SendBatch<'a, T> {
+    sender: &'a mut Sender<T>,
+    batch: Option<Vec<T>>,
+}
+
+/// Future for the [`recv_many`](Receiver::recv_many) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct RecvMany<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    buf: &'a mut Vec<T>,
+    limit: usize,
+    done: bool,
+}
+
+// Pin is never projected to the fields
+impl<T> Unpin for SendBatch<'_, T> {}
+impl<T> Unpin for RecvMany<'_, T> {}
+
+impl<'a, T> SendBatch<'a, T> {
+    pub(super) fn new(sender: &'a mut Sender<T>, batch: Vec<T>) -> SendBatch<'a, T> {
+        SendBatch { sender, batch: Some(batch) }
+    }
+}
+
+impl<'a, T> RecvMany<'a, T> {
+    pub(super) fn new(
+        receiver: &'a mut Receiver<T>,
+        buf: &'a mut Vec<T>,
+        limit: usize,
+    ) -> RecvMany<'a, T> {
+        RecvMany { receiver, buf, limit, done: false }
+    }
+}
+
+impl<T> Future for SendBatch<'_, T> {
+    type Output = Result<(), SendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        assert!(this.batch.is_some(), "polled SendBatch after completion");
+        loop {
+            match this.sender.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    let batch = this.batch.take().unwrap();
+                    match this.sender.try_send_batch(batch) {
+                        Ok(()) => return Poll::Ready(Ok(())),
+                        Err(e) => {
+                            if !e.is_full() {
+                                return Poll::Ready(Err(e.err));
+                            }
+                            // Part of the batch was sent, and the sender was
+                            // parked, so keep the rest for later
+                            this.batch = Some(e.into_inner());
+                        }
+                    }
+                }
+                Poll::Ready(Err(e)) => {
+                    this.batch = None;
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T> FusedFuture for SendBatch<'_, T> {
+    fn is_terminated(&self) -> bool {
+        self.batch.is_none()
+    }
+}
+
+impl<T> Future for RecvMany<'_, T> {
+    type Output = usize;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        let this = &mut *self;
+        assert!(!this.done, "polled RecvMany after completion");
+        let res = this.receiver.poll_recv_many(cx, this.buf, this.limit);
+        if res.is_ready() {
+            this.done = true;
+        }
+        res
+    }
+}
+
+impl<T> FusedFuture for RecvMany<'_, T> {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
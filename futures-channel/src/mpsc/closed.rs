@@ -0,0 +1,134 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{Sender, SenderInner, UnboundedSender};
+
+/// Future for the [`closed`](Sender::closed) method of bounded and unbounded
+/// senders.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Closed<'a, T> {
+    // `None` once the channel has been closed
+    sender: Option<&'a SenderInner<T>>,
+    // Identifies this future in `Inner::closed_tasks`
+    key: usize,
+}
+
+// Pin is never projected to the fields
+impl<T> Unpin for Closed<'_, T> {}
+
+// Identifies a task waiting for the channel to close in
+// `Inner::closed_tasks`: each sender keeps the task which last called its
+// `poll_closed` method, and each `Closed` future keeps its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) enum ClosedKey {
+    Sender(usize),
+    Future(usize),
+}
+
+impl<'a, T> Closed<'a, T> {
+    fn new(sender: Option<&'a SenderInner<T>>) -> Closed<'a, T> {
+        static NEXT_KEY: AtomicUsize = AtomicUsize::new(0);
+        Closed {
+            sender,
+            key: NEXT_KEY.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl<T> SenderInner<T> {
+    pub(super) fn closed_key(&self) -> ClosedKey {
+        ClosedKey::Sender(&*self.sender_task as *const _ as usize)
+    }
+
+    fn poll_closed(&self, key: ClosedKey, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_closed() {
+            return Poll::Ready(());
+        }
+        self.inner.closed_tasks.lock().unwrap().insert(key, cx.waker().clone());
+        // Check again, in case the channel was closed before the task was
+        // registered
+        if self.is_closed() {
+            self.inner.closed_tasks.lock().unwrap().remove(&key);
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Polls the channel to determine whether it has been closed, which
+    /// happens when the [`Receiver`](super::Receiver) is dropped or
+    /// [`close`](super::Receiver::close)d, or when a sender calls
+    /// [`close_channel`](Sender::close_channel).
+    ///
+    /// If the channel is still open, the current task is scheduled to be
+    /// notified once it closes.
+    pub fn poll_closed(&self, cx: &mut Context<'_>) -> Poll<()> {
+        match &self.0 {
+            Some(inner) => inner.poll_closed(inner.closed_key(), cx),
+            None => Poll::Ready(()),
+        }
+    }
+
+    /// Returns a future which resolves once the channel has been closed.
+    ///
+    /// See [`poll_closed`](Sender::poll_closed).
+    pub fn closed(&self) -> Closed<'_, T> {
+        Closed::new(self.0.as_ref())
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Polls the channel to determine whether it has been closed, which
+    /// happens when the [`UnboundedReceiver`](super::UnboundedReceiver) is
+    /// dropped or [`close`](super::UnboundedReceiver::close)d, or when a
+    /// sender calls [`close_channel`](UnboundedSender::close_channel).
+    ///
+    /// If the channel is still open, the current task is scheduled to be
+    /// notified once it closes.
+    pub fn poll_closed(&self, cx: &mut Context<'_>) -> Poll<()> {
+        match &self.0 {
+            Some(inner) => inner.poll_closed(inner.closed_key(), cx),
+            None => Poll::Ready(()),
+        }
+    }
+
+    /// Returns a future which resolves once the channel has been closed.
+    ///
+    /// See [`poll_closed`](UnboundedSender::poll_closed).
+    pub fn closed(&self) -> Closed<'_, T> {
+        Closed::new(self.0.as_ref())
+    }
+}
+
+impl<T> Future for Closed<'_, T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(sender) = self.sender {
+            if sender.poll_closed(ClosedKey::Future(self.key), cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+        self.sender = None;
+        Poll::Ready(())
+    }
+}
+
+impl<T> FusedFuture for Closed<'_, T> {
+    fn is_terminated(&self) -> bool {
+        self.sender.is_none()
+    }
+}
+
+impl<T> Drop for Closed<'_, T> {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender {
+            sender.inner.closed_tasks.lock().unwrap().remove(&ClosedKey::Future(self.key));
+        }
+    }
+}
@@ -82,6 +82,8 @@ use futures_core::stream::{FusedStream, Stream};
 use futures_core::task::{Context, Poll, Waker};
 use futures_core::task::__internal::AtomicWaker;
 use std::any::Any;
+use std::cmp;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::pin::Pin;
@@ -94,6 +96,19 @@ use crate::mpsc::queue::Queue;
 
 mod queue;
 
+mod batch;
+pub use self::batch::{RecvMany, SendBatch};
+
+mod permit;
+pub use self::permit::{OwnedPermit, Permit, Reserve, ReserveOwned};
+
+mod weak;
+pub use self::weak::{WeakSender, WeakUnboundedSender};
+
+mod closed;
+pub use self::closed::Closed;
+use self::closed::ClosedKey;
+
 #[derive(Debug)]
 struct SenderInner<T> {
     // Channel state shared between the sender and receiver.
@@ -147,25 +162,25 @@ impl<T> Unpin for UnboundedReceiver<T> {}
 /// The error type for [`Sender`s](Sender) used as `Sink`s.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SendError {
-    kind: SendErrorKind,
+    pub(crate) kind: SendErrorKind,
 }
 
 /// The error type returned from [`try_send`](Sender::try_send).
 #[derive(Clone, PartialEq, Eq)]
 pub struct TrySendError<T> {
-    err: SendError,
-    val: T,
+    pub(crate) err: SendError,
+    pub(crate) val: T,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-enum SendErrorKind {
+pub(crate) enum SendErrorKind {
     Full,
     Disconnected,
 }
 
 /// The error type returned from [`try_next`](Receiver::try_next).
 pub struct TryRecvError {
-    _inner: (),
+    pub(crate) _inner: (),
 }
 
 impl fmt::Display for SendError {
@@ -295,6 +310,9 @@ struct Inner<T> {
 
     // Handle to the receiver's task.
     recv_task: AtomicWaker,
+
+    // Handles to the tasks waiting for the channel to close
+    closed_tasks: Mutex<HashMap<ClosedKey, Waker>>,
 }
 
 // Struct representation of `Inner::state`.
@@ -387,6 +405,7 @@ fn channel2<T>(buffer: Option<usize>) -> (SenderInner<T>, Receiver<T>) {
         parked_queue: Queue::new(),
         num_senders: AtomicUsize::new(1),
         recv_task: AtomicWaker::new(),
+        closed_tasks: Mutex::new(HashMap::new()),
     });
 
     let tx = SenderInner {
@@ -442,7 +461,7 @@ impl<T> SenderInner<T> {
         // None is returned in the case that the channel has been closed by the
         // receiver. This happens when `Receiver::close` is called or the
         // receiver is dropped.
-        let park_self = match self.inc_num_messages() {
+        let park_self = match self.inc_num_messages(1) {
             Some(num_messages) => {
                 // Block if the current number of pending messages has exceeded
                 // the configured buffer size
@@ -472,6 +491,55 @@ impl<T> SenderInner<T> {
         Ok(())
     }
 
+    /// Attempts to send a batch of messages, returning the ones which could
+    /// not be sent if there was an error.
+    fn try_send_batch(&mut self, mut msgs: Vec<T>) -> Result<(), TrySendError<Vec<T>>> {
+        if !self.poll_unparked(None).is_ready() {
+            return Err(TrySendError {
+                err: SendError {
+                    kind: SendErrorKind::Full,
+                },
+                val: msgs,
+            });
+        }
+        if msgs.is_empty() && !self.is_closed() {
+            return Ok(());
+        }
+
+        // Acquire capacity for as many messages as fit in the buffer, plus
+        // the sender's guaranteed slot. If the batch takes up that slot, the
+        // sender is parked until one of the messages is received.
+        let (num_sent, park_self) = match self.inc_num_messages_up_to(msgs.len()) {
+            Some((num_sent, num_messages)) => {
+                (num_sent, num_messages > self.inner.buffer.unwrap())
+            }
+            None => return Err(TrySendError {
+                err: SendError {
+                    kind: SendErrorKind::Disconnected,
+                },
+                val: msgs,
+            }),
+        };
+
+        if park_self {
+            self.park();
+        }
+
+        let rest = msgs.split_off(num_sent);
+        self.queue_push_batch_and_signal(msgs);
+
+        if rest.is_empty() {
+            Ok(())
+        } else {
+            Err(TrySendError {
+                err: SendError {
+                    kind: SendErrorKind::Full,
+                },
+                val: rest,
+            })
+        }
+    }
+
     fn poll_ready_nb(&self) -> Poll<Result<(), SendError>> {
         let state = decode_state(self.inner.state.load(SeqCst));
         if state.is_open {
@@ -494,8 +562,18 @@ impl<T> SenderInner<T> {
         self.inner.recv_task.wake();
     }
 
-    // Increment the number of queued messages. Returns the resulting number.
-    fn inc_num_messages(&self) -> Option<usize> {
+    // Push a batch of messages to the queue and signal to the receiver once
+    fn queue_push_batch_and_signal(&self, msgs: Vec<T>) {
+        for msg in msgs {
+            self.inner.message_queue.push(msg);
+        }
+
+        self.inner.recv_task.wake();
+    }
+
+    // Increment the number of queued messages by `n`. Returns the resulting
+    // number.
+    fn inc_num_messages(&self, n: usize) -> Option<usize> {
         let mut curr = self.inner.state.load(SeqCst);
 
         loop {
@@ -509,10 +587,10 @@ impl<T> SenderInner<T> {
             // This probably is never hit? Odds are the process will run out of
             // memory first. It may be worth to return something else in this
             // case?
-            assert!(state.num_messages < MAX_CAPACITY, "buffer space \
+            assert!(n <= MAX_CAPACITY - state.num_messages, "buffer space \
                     exhausted; sending this messages would overflow the state");
 
-            state.num_messages += 1;
+            state.num_messages += n;
 
             let next = encode_state(&state);
             match self.inner.state.compare_exchange(curr, next, SeqCst, SeqCst) {
@@ -524,6 +602,37 @@ impl<T> SenderInner<T> {
         }
     }
 
+    // Increment the number of queued messages by at most `n`, stopping once
+    // the buffer and the sender's guaranteed slot are full. Returns how many
+    // messages were counted and the resulting number.
+    fn inc_num_messages_up_to(&self, n: usize) -> Option<(usize, usize)> {
+        let buffer = self.inner.buffer.unwrap();
+        let mut curr = self.inner.state.load(SeqCst);
+
+        loop {
+            let mut state = decode_state(curr);
+
+            // The receiver end closed the channel.
+            if !state.is_open {
+                return None;
+            }
+
+            let num_sent = cmp::min(n, buffer.saturating_sub(state.num_messages) + 1);
+            assert!(num_sent <= MAX_CAPACITY - state.num_messages, "buffer space \
+                    exhausted; sending this messages would overflow the state");
+
+            state.num_messages += num_sent;
+
+            let next = encode_state(&state);
+            match self.inner.state.compare_exchange(curr, next, SeqCst, SeqCst) {
+                Ok(_) => {
+                    return Some((num_sent, state.num_messages))
+                }
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
     fn park(&mut self) {
         {
             let mut sender = self.sender_task.lock().unwrap();
@@ -635,6 +744,36 @@ impl<T> Sender<T> {
             .map_err(|e| e.err)
     }
 
+    /// Attempts to send a batch of messages on this `Sender`, returning the
+    /// messages which could not be sent if there was an error.
+    ///
+    /// The batch is sent in order for as long as the channel has capacity,
+    /// the last message possibly taking up this sender's guaranteed slot.
+    /// If the whole batch does not fit, the messages sent so far stay in the
+    /// channel and the rest of the batch is returned in a
+    /// [full](TrySendError::is_full) error.
+    pub fn try_send_batch(&mut self, batch: Vec<T>) -> Result<(), TrySendError<Vec<T>>> {
+        if let Some(inner) = &mut self.0 {
+            inner.try_send_batch(batch)
+        } else {
+            Err(TrySendError {
+                err: SendError {
+                    kind: SendErrorKind::Disconnected,
+                },
+                val: batch,
+            })
+        }
+    }
+
+    /// Sends a batch of messages on the channel, waiting until there is
+    /// capacity for it.
+    ///
+    /// The batch may be sent in several parts, as capacity frees up; see
+    /// [`try_send_batch`](Sender::try_send_batch).
+    pub fn send_batch(&mut self, batch: Vec<T>) -> SendBatch<'_, T> {
+        SendBatch::new(self, batch)
+    }
+
     /// Polls the channel to determine if there is guaranteed capacity to send
     /// at least one item without waiting.
     ///
@@ -657,6 +796,22 @@ impl<T> Sender<T> {
         inner.poll_ready(cx)
     }
 
+    /// Waits for a slot in the channel, which is then held by the returned
+    /// [`Permit`](Permit) until a message is sent through it.
+    ///
+    /// This allows reserving capacity before building the message. The
+    /// future resolves to an error if the receiver has been dropped.
+    pub fn reserve(&mut self) -> Reserve<'_, T> {
+        Reserve::new(self)
+    }
+
+    /// Waits for a slot in the channel, like [`reserve`](Sender::reserve),
+    /// but takes the sender by value and resolves to an
+    /// [`OwnedPermit`](OwnedPermit) which holds on to it.
+    pub fn reserve_owned(self) -> ReserveOwned<T> {
+        ReserveOwned::new(self)
+    }
+
     /// Returns whether this channel is closed without needing a context.
     pub fn is_closed(&self) -> bool {
         self.0.as_ref().map(SenderInner::is_closed).unwrap_or(true)
@@ -673,6 +828,40 @@ impl<T> Sender<T> {
     pub fn disconnect(&mut self) {
         self.0 = None;
     }
+
+    /// Returns the number of messages in the channel which have not been
+    /// received yet.
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map(|inner| inner.inner.num_messages()).unwrap_or(0)
+    }
+
+    /// Returns whether there are no messages waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `buffer` size the channel was created with.
+    ///
+    /// On top of this, each sender has a guaranteed slot in the channel (see
+    /// [`channel`](channel)). Returns zero if this sender has been
+    /// disconnected.
+    pub fn capacity(&self) -> usize {
+        self.0.as_ref().and_then(|inner| inner.inner.buffer).unwrap_or(0)
+    }
+
+    /// Returns the number of senders connected to the channel, including
+    /// this one, or zero if this sender has been disconnected.
+    pub fn sender_count(&self) -> usize {
+        self.0.as_ref().map(|inner| inner.inner.num_senders.load(SeqCst)).unwrap_or(0)
+    }
+
+    /// Returns whether this sender and `other` send into the same channel.
+    pub fn same_receiver(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(inner), Some(other)) => Arc::ptr_eq(&inner.inner, &other.inner),
+            _ => false,
+        }
+    }
 }
 
 impl<T> UnboundedSender<T> {
@@ -704,10 +893,41 @@ impl<T> UnboundedSender<T> {
         self.0 = None;
     }
 
+    /// Returns the number of messages in the channel which have not been
+    /// received yet.
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map(|inner| inner.inner.num_messages()).unwrap_or(0)
+    }
+
+    /// Returns whether there are no messages waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the channel, which is always `None` as the
+    /// channel is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the number of senders connected to the channel, including
+    /// this one, or zero if this sender has been disconnected.
+    pub fn sender_count(&self) -> usize {
+        self.0.as_ref().map(|inner| inner.inner.num_senders.load(SeqCst)).unwrap_or(0)
+    }
+
+    /// Returns whether this sender and `other` send into the same channel.
+    pub fn same_receiver(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(inner), Some(other)) => Arc::ptr_eq(&inner.inner, &other.inner),
+            _ => false,
+        }
+    }
+
     // Do the send without parking current task.
     fn do_send_nb(&self, msg: T) -> Result<(), TrySendError<T>> {
         if let Some(inner) = &self.0 {
-            if inner.inc_num_messages().is_some() {
+            if inner.inc_num_messages(1).is_some() {
                 inner.queue_push_and_signal(msg);
                 return Ok(());
             }
@@ -738,6 +958,24 @@ impl<T> UnboundedSender<T> {
     pub fn unbounded_send(&self, msg: T) -> Result<(), TrySendError<T>> {
         self.do_send_nb(msg)
     }
+
+    /// Sends a batch of messages along this channel, signalling the receiver
+    /// only once.
+    pub fn unbounded_send_batch(&self, batch: Vec<T>) -> Result<(), TrySendError<Vec<T>>> {
+        if let Some(inner) = &self.0 {
+            if inner.inc_num_messages(batch.len()).is_some() {
+                inner.queue_push_batch_and_signal(batch);
+                return Ok(());
+            }
+        }
+
+        Err(TrySendError {
+            err: SendError {
+                kind: SendErrorKind::Disconnected,
+            },
+            val: batch,
+        })
+    }
 }
 
 impl<T> Clone for Sender<T> {
@@ -787,6 +1025,8 @@ impl<T> Clone for SenderInner<T> {
 
 impl<T> Drop for SenderInner<T> {
     fn drop(&mut self) {
+        self.inner.closed_tasks.lock().unwrap().remove(&self.closed_key());
+
         // Ordering between variables don't matter here
         let prev = self.inner.num_senders.fetch_sub(1, SeqCst);
 
@@ -836,6 +1076,101 @@ impl<T> Receiver<T> {
         }
     }
 
+    /// Returns the number of messages in the channel which have not been
+    /// received yet.
+    pub fn len(&self) -> usize {
+        self.inner.as_ref().map(|inner| inner.num_messages()).unwrap_or(0)
+    }
+
+    /// Returns whether there are no messages waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `buffer` size the channel was created with.
+    ///
+    /// On top of this, each sender has a guaranteed slot in the channel (see
+    /// [`channel`](channel)). Returns zero once the stream has ended.
+    pub fn capacity(&self) -> usize {
+        self.inner.as_ref().and_then(|inner| inner.buffer).unwrap_or(0)
+    }
+
+    /// Returns the number of senders connected to the channel.
+    pub fn sender_count(&self) -> usize {
+        self.inner.as_ref().map(|inner| inner.num_senders.load(SeqCst)).unwrap_or(0)
+    }
+
+    /// Polls for up to `limit` messages, appending them to `buf`.
+    ///
+    /// Returns the number of messages received, which is only zero if
+    /// `limit` is zero or once all senders have been dropped and every
+    /// message has been received. When no message is available yet, the
+    /// current task is notified once there is.
+    ///
+    /// Taking messages out in batches is cheaper than one at a time, as the
+    /// channel state is updated only once per batch.
+    pub fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut Vec<T>,
+        limit: usize,
+    ) -> Poll<usize> {
+        if limit == 0 {
+            return Poll::Ready(0);
+        }
+        match self.next_messages(buf, limit) {
+            Poll::Ready(n) => Poll::Ready(n),
+            Poll::Pending => {
+                // Same as in `poll_next`: park, then check the queue again.
+                self.inner.as_ref().unwrap().recv_task.register(cx.waker());
+                self.next_messages(buf, limit)
+            }
+        }
+    }
+
+    /// Receives up to `limit` messages, appending them to `buf`.
+    ///
+    /// The returned future waits for at least one message, and resolves to
+    /// the number of messages received, or to zero if the channel is
+    /// exhausted. See [`poll_recv_many`](Receiver::poll_recv_many).
+    pub fn recv_many<'a>(&'a mut self, buf: &'a mut Vec<T>, limit: usize) -> RecvMany<'a, T> {
+        RecvMany::new(self, buf, limit)
+    }
+
+    fn next_messages(&mut self, buf: &mut Vec<T>, limit: usize) -> Poll<usize> {
+        let inner = match self.inner.as_mut() {
+            Some(inner) => inner,
+            None => return Poll::Ready(0),
+        };
+        let mut received = 0;
+        while received < limit {
+            match unsafe { inner.message_queue.pop_spin() } {
+                Some(msg) => {
+                    buf.push(msg);
+                    received += 1;
+                    // One parked sender is released per message, as in
+                    // `next_message`
+                    if let Some(task) = unsafe { inner.parked_queue.pop_spin() } {
+                        task.lock().unwrap().notify();
+                    }
+                }
+                None => break,
+            }
+        }
+        if received > 0 {
+            inner.state.fetch_sub(received, SeqCst);
+            return Poll::Ready(received);
+        }
+        match self.next_message() {
+            Poll::Ready(Some(msg)) => {
+                buf.push(msg);
+                Poll::Ready(1)
+            }
+            Poll::Ready(None) => Poll::Ready(0),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
     fn next_message(&mut self) -> Poll<Option<T>> {
         let inner = self.inner.as_mut().expect("Receiver::next_message called after `None`");
         // Pop off a message
@@ -958,6 +1293,47 @@ impl<T> UnboundedReceiver<T> {
     pub fn try_next(&mut self) -> Result<Option<T>, TryRecvError> {
         self.0.try_next()
     }
+
+    /// Returns the number of messages in the channel which have not been
+    /// received yet.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether there are no messages waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the channel, which is always `None` as the
+    /// channel is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the number of senders connected to the channel.
+    pub fn sender_count(&self) -> usize {
+        self.0.sender_count()
+    }
+
+    /// Polls for up to `limit` messages, appending them to `buf`.
+    ///
+    /// See [`Receiver::poll_recv_many`](Receiver::poll_recv_many).
+    pub fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut Vec<T>,
+        limit: usize,
+    ) -> Poll<usize> {
+        self.0.poll_recv_many(cx, buf, limit)
+    }
+
+    /// Receives up to `limit` messages, appending them to `buf`.
+    ///
+    /// See [`Receiver::recv_many`](Receiver::recv_many).
+    pub fn recv_many<'a>(&'a mut self, buf: &'a mut Vec<T>, limit: usize) -> RecvMany<'a, T> {
+        RecvMany::new(&mut self.0, buf, limit)
+    }
 }
 
 impl<T> FusedStream for UnboundedReceiver<T> {
@@ -1001,6 +1377,16 @@ impl<T> Inner<T> {
         }
 
         self.state.fetch_and(!OPEN_MASK, SeqCst);
+
+        // Notify the senders waiting for the channel to close
+        for (_, task) in self.closed_tasks.lock().unwrap().drain() {
+            task.wake();
+        }
+    }
+
+    // The number of messages sent and not received yet
+    fn num_messages(&self) -> usize {
+        decode_state(self.state.load(SeqCst)).num_messages
     }
 }
 
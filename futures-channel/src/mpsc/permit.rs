@@ -0,0 +1,133 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use std::pin::Pin;
+
+use super::{SendError, Sender};
+
+/// Future for the [`reserve`](Sender::reserve) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Reserve<'a, T> {
+    sender: Option<&'a mut Sender<T>>,
+}
+
+/// Future for the [`reserve_owned`](Sender::reserve_owned) method.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct ReserveOwned<T> {
+    sender: Option<Sender<T>>,
+}
+
+/// A slot in the channel, reserved by [`Sender::reserve`](Sender::reserve).
+///
+/// Sending a message through the permit cannot fail for lack of capacity.
+/// Dropping the permit without sending leaves the slot to the sender for a
+/// later send.
+#[derive(Debug)]
+pub struct Permit<'a, T> {
+    sender: &'a mut Sender<T>,
+}
+
+/// A slot in the channel, reserved by
+/// [`Sender::reserve_owned`](Sender::reserve_owned).
+///
+/// This is the owned equivalent of [`Permit`](Permit), which holds on to the
+/// sender instead of borrowing it.
+#[derive(Debug)]
+pub struct OwnedPermit<T> {
+    sender: Sender<T>,
+}
+
+// Pin is never projected to the fields
+impl<T> Unpin for Reserve<'_, T> {}
+impl<T> Unpin for ReserveOwned<T> {}
+
+impl<'a, T> Reserve<'a, T> {
+    pub(super) fn new(sender: &'a mut Sender<T>) -> Reserve<'a, T> {
+        Reserve { sender: Some(sender) }
+    }
+}
+
+impl<T> ReserveOwned<T> {
+    pub(super) fn new(sender: Sender<T>) -> ReserveOwned<T> {
+        ReserveOwned { sender: Some(sender) }
+    }
+}
+
+impl<'a, T> Future for Reserve<'a, T> {
+    type Output = Result<Permit<'a, T>, SendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let sender = self.sender.as_mut().expect("polled Reserve after completion");
+        match sender.poll_ready(cx) {
+            Poll::Ready(Ok(())) => {
+                let sender = self.sender.take().unwrap();
+                Poll::Ready(Ok(Permit { sender }))
+            }
+            Poll::Ready(Err(e)) => {
+                self.sender = None;
+                Poll::Ready(Err(e))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> FusedFuture for Reserve<'_, T> {
+    fn is_terminated(&self) -> bool {
+        self.sender.is_none()
+    }
+}
+
+impl<T> Future for ReserveOwned<T> {
+    type Output = Result<OwnedPermit<T>, SendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let sender = self.sender.as_mut().expect("polled ReserveOwned after completion");
+        match sender.poll_ready(cx) {
+            Poll::Ready(Ok(())) => {
+                let sender = self.sender.take().unwrap();
+                Poll::Ready(Ok(OwnedPermit { sender }))
+            }
+            Poll::Ready(Err(e)) => {
+                self.sender = None;
+                Poll::Ready(Err(e))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> FusedFuture for ReserveOwned<T> {
+    fn is_terminated(&self) -> bool {
+        self.sender.is_none()
+    }
+}
+
+impl<T> Permit<'_, T> {
+    /// Sends a message using the reserved slot.
+    ///
+    /// If the channel was closed since the slot was reserved, the message is
+    /// dropped.
+    pub fn send(self, msg: T) {
+        // Having been reserved, the slot is there unless the channel is closed
+        let _ = self.sender.start_send(msg);
+    }
+}
+
+impl<T> OwnedPermit<T> {
+    /// Sends a message using the reserved slot, and gives back the sender.
+    ///
+    /// If the channel was closed since the slot was reserved, the message is
+    /// dropped.
+    pub fn send(mut self, msg: T) -> Sender<T> {
+        // Having been reserved, the slot is there unless the channel is closed
+        let _ = self.sender.start_send(msg);
+        self.sender
+    }
+
+    /// Gives up the reserved slot without sending, and gives back the sender.
+    pub fn release(self) -> Sender<T> {
+        self.sender
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::Ordering::SeqCst;
+
+use super::{Inner, Sender, SenderInner, SenderTask, UnboundedSender};
+
+/// A sender which does not keep a bounded channel open.
+///
+/// This is created by the [`downgrade`](Sender::downgrade) method, and is not
+/// counted as a sender: once all [`Sender`](Sender) handles have been
+/// dropped, the receiver's stream ends even if weak senders remain. To send,
+/// a weak sender has to be [`upgrade`](WeakSender::upgrade)d first, which
+/// only succeeds while some `Sender` is still alive.
+#[derive(Debug)]
+pub struct WeakSender<T>(Option<Weak<Inner<T>>>);
+
+/// A sender which does not keep an unbounded channel open.
+///
+/// This is created by the [`downgrade`](UnboundedSender::downgrade) method.
+/// See [`WeakSender`](WeakSender).
+#[derive(Debug)]
+pub struct WeakUnboundedSender<T>(Option<Weak<Inner<T>>>);
+
+impl<T> SenderInner<T> {
+    fn downgrade(&self) -> Weak<Inner<T>> {
+        Arc::downgrade(&self.inner)
+    }
+
+    fn upgrade(weak: &Weak<Inner<T>>) -> Option<SenderInner<T>> {
+        let inner = weak.upgrade()?;
+        let mut curr = inner.num_senders.load(SeqCst);
+
+        loop {
+            // Once the last sender is gone the stream has ended, so the
+            // channel must not be revived.
+            if curr == 0 {
+                return None;
+            }
+            if curr == inner.max_senders() {
+                panic!("cannot upgrade `WeakSender` -- too many outstanding senders");
+            }
+
+            match inner.num_senders.compare_exchange(curr, curr + 1, SeqCst, SeqCst) {
+                Ok(_) => {
+                    return Some(SenderInner {
+                        inner,
+                        sender_task: Arc::new(Mutex::new(SenderTask::new())),
+                        maybe_parked: false,
+                    })
+                }
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Creates a [`WeakSender`](WeakSender) for this channel, which does not
+    /// keep it open.
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender(self.0.as_ref().map(SenderInner::downgrade))
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Creates a [`WeakUnboundedSender`](WeakUnboundedSender) for this
+    /// channel, which does not keep it open.
+    pub fn downgrade(&self) -> WeakUnboundedSender<T> {
+        WeakUnboundedSender(self.0.as_ref().map(SenderInner::downgrade))
+    }
+}
+
+impl<T> WeakSender<T> {
+    /// Tries to get a [`Sender`](Sender) for the channel.
+    ///
+    /// Returns `None` if all senders have been dropped.
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        let inner = SenderInner::upgrade(self.0.as_ref()?)?;
+        Some(Sender(Some(inner)))
+    }
+}
+
+impl<T> WeakUnboundedSender<T> {
+    /// Tries to get an [`UnboundedSender`](UnboundedSender) for the channel.
+    ///
+    /// Returns `None` if all senders have been dropped.
+    pub fn upgrade(&self) -> Option<UnboundedSender<T>> {
+        let inner = SenderInner::upgrade(self.0.as_ref()?)?;
+        Some(UnboundedSender(Some(inner)))
+    }
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> WeakSender<T> {
+        WeakSender(self.0.clone())
+    }
+}
+
+impl<T> Clone for WeakUnboundedSender<T> {
+    fn clone(&self) -> WeakUnboundedSender<T> {
+        WeakUnboundedSender(self.0.clone())
+    }
+}
@@ -0,0 +1,457 @@
+//! A multi-producer, single-consumer queue which delivers messages by
+//! priority.
+//!
+//! Each message is sent along with a priority, which can be any type
+//! implementing [`Ord`](std::cmp::Ord). The [`Receiver`](Receiver) always
+//! yields the pending message with the highest priority, so that, for
+//! example, control messages can overtake bulk data sent earlier. Messages
+//! with equal priorities are received in the order they were sent.
+//!
+//! The channel is bounded, with the same backpressure as
+//! [`mpsc::channel`](crate::mpsc::channel): its capacity is `buffer` plus the
+//! number of senders, and a [`Sender`](Sender) which sent a message beyond
+//! the buffer has to wait until a message has been received before it can
+//! send again.
+//!
+//! # Disconnection
+//!
+//! When all [`Sender`](Sender) handles have been dropped, the receiver still
+//! gets the messages remaining in the channel, after which its stream ends.
+//! When the [`Receiver`](Receiver) has been dropped or
+//! [`close`](Receiver::close)d, all further attempts to send result in an
+//! error.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::channel::priority;
+//! use futures::executor::block_on_stream;
+//!
+//! let (mut tx, rx) = priority::channel(4);
+//! tx.try_send(0, "bulk 1").unwrap();
+//! tx.try_send(0, "bulk 2").unwrap();
+//! tx.try_send(9, "control").unwrap();
+//! drop(tx);
+//!
+//! let got: Vec<_> = block_on_stream(rx).collect();
+//! assert_eq!(got, vec!["control", "bulk 1", "bulk 2"]);
+//! ```
+
+use futures_core::stream::{FusedStream, Stream};
+use futures_core::task::{Context, Poll, Waker};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::mpsc::SendErrorKind;
+pub use crate::mpsc::{SendError, TryRecvError, TrySendError};
+
+/// The transmission end of a priority channel.
+///
+/// This is created by the [`channel`](channel) function, and can be cloned
+/// to get more senders.
+#[derive(Debug)]
+pub struct Sender<P, T>(Option<SenderInner<P, T>>);
+
+/// The receiving end of a priority channel.
+///
+/// This is created by the [`channel`](channel) function.
+#[derive(Debug)]
+pub struct Receiver<P, T> {
+    shared: Arc<Shared<P, T>>,
+    terminated: bool,
+}
+
+// The channel does not ever project Pin to the inner P or T
+impl<P, T> Unpin for Sender<P, T> {}
+impl<P, T> Unpin for Receiver<P, T> {}
+
+#[derive(Debug)]
+struct SenderInner<P, T> {
+    shared: Arc<Shared<P, T>>,
+
+    // Handle to the task that is blocked on this sender. This handle is sent
+    // to the receiver via the parked queue.
+    sender_task: Arc<Mutex<SenderTask>>,
+
+    // True if the sender might be blocked. This is an optimization to avoid
+    // having to lock the mutex most of the time.
+    maybe_parked: bool,
+}
+
+#[derive(Debug)]
+struct SenderTask {
+    task: Option<Waker>,
+    is_parked: bool,
+}
+
+#[derive(Debug)]
+struct Shared<P, T> {
+    state: Mutex<State<P, T>>,
+    buffer: usize,
+}
+
+#[derive(Debug)]
+struct State<P, T> {
+    queue: BinaryHeap<Entry<P, T>>,
+    // Sequence number of the next message, to keep messages of equal
+    // priority in order
+    next_seq: u64,
+    // Cleared when the channel is closed by the receiver, or from the
+    // sending side by `close_channel`
+    open: bool,
+    num_senders: usize,
+    // Senders which sent a message beyond the buffer, in the order they did
+    // so. One of them is unparked for each message received.
+    parked: VecDeque<Arc<Mutex<SenderTask>>>,
+    recv_task: Option<Waker>,
+}
+
+struct Entry<P, T> {
+    priority: P,
+    seq: u64,
+    msg: T,
+}
+
+/// Creates a bounded priority channel for communicating between
+/// asynchronous tasks.
+///
+/// As with [`mpsc::channel`](crate::mpsc::channel), the channel's capacity is
+/// equal to `buffer + num-senders`.
+///
+/// The [`Receiver`](Receiver) returned implements the
+/// [`Stream`](futures_core::stream::Stream) trait, while [`Sender`](Sender)
+/// implements `Sink` for `(priority, message)` pairs.
+pub fn channel<P: Ord, T>(buffer: usize) -> (Sender<P, T>, Receiver<P, T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+            open: true,
+            num_senders: 1,
+            parked: VecDeque::new(),
+            recv_task: None,
+        }),
+        buffer,
+    });
+    let rx = Receiver { shared: shared.clone(), terminated: false };
+    let tx = SenderInner {
+        shared,
+        sender_task: Arc::new(Mutex::new(SenderTask::new())),
+        maybe_parked: false,
+    };
+    (Sender(Some(tx)), rx)
+}
+
+impl SenderTask {
+    fn new() -> Self {
+        SenderTask {
+            task: None,
+            is_parked: false,
+        }
+    }
+
+    fn notify(&mut self) {
+        self.is_parked = false;
+
+        if let Some(task) = self.task.take() {
+            task.wake();
+        }
+    }
+}
+
+impl<P, T> Shared<P, T> {
+    fn lock(&self) -> MutexGuard<'_, State<P, T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl<P, T> State<P, T> {
+    fn wake_receiver(&mut self) {
+        if let Some(task) = self.recv_task.take() {
+            task.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+
+        // Wake up the parked senders as they'll see that the channel is
+        // closed, and the receiver in case it waits for a message that will
+        // never come.
+        while let Some(task) = self.parked.pop_front() {
+            task.lock().unwrap().notify();
+        }
+        self.wake_receiver();
+    }
+}
+
+fn disconnected() -> SendError {
+    SendError {
+        kind: SendErrorKind::Disconnected,
+    }
+}
+
+impl<P: Ord, T> SenderInner<P, T> {
+    fn try_send(&mut self, priority: P, msg: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.shared.lock();
+        if !state.open {
+            return Err(TrySendError {
+                err: disconnected(),
+                val: msg,
+            });
+        }
+
+        // If the sender is currently blocked, reject the message
+        if self.maybe_parked && self.sender_task.lock().unwrap().is_parked {
+            return Err(TrySendError {
+                err: SendError {
+                    kind: SendErrorKind::Full,
+                },
+                val: msg,
+            });
+        }
+        self.maybe_parked = false;
+
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.queue.push(Entry { priority, seq, msg });
+        if state.queue.len() > self.shared.buffer {
+            // The buffer is full, so this sender has used its guaranteed slot
+            // and must wait for a message to be received before sending again.
+            {
+                let mut task = self.sender_task.lock().unwrap();
+                task.task = None;
+                task.is_parked = true;
+            }
+            state.parked.push_back(self.sender_task.clone());
+            self.maybe_parked = true;
+        }
+
+        state.wake_receiver();
+        Ok(())
+    }
+}
+
+impl<P, T> SenderInner<P, T> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
+        let state = self.shared.lock();
+        if !state.open {
+            return Poll::Ready(Err(disconnected()));
+        }
+        if self.maybe_parked {
+            let mut task = self.sender_task.lock().unwrap();
+            if task.is_parked {
+                // Update the task in case the `Sender` has been moved to
+                // another task
+                task.task = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            self.maybe_parked = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<P, T> Clone for SenderInner<P, T> {
+    fn clone(&self) -> SenderInner<P, T> {
+        self.shared.lock().num_senders += 1;
+        SenderInner {
+            shared: self.shared.clone(),
+            sender_task: Arc::new(Mutex::new(SenderTask::new())),
+            maybe_parked: false,
+        }
+    }
+}
+
+impl<P, T> Drop for SenderInner<P, T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.num_senders -= 1;
+        if state.num_senders == 0 {
+            // let the receiver observe the end of the stream
+            state.wake_receiver();
+        }
+    }
+}
+
+impl<P: Ord, T> Sender<P, T> {
+    /// Attempts to send a message with the given priority on this `Sender`,
+    /// returning the message if there was an error.
+    pub fn try_send(&mut self, priority: P, msg: T) -> Result<(), TrySendError<T>> {
+        if let Some(inner) = &mut self.0 {
+            inner.try_send(priority, msg)
+        } else {
+            Err(TrySendError {
+                err: disconnected(),
+                val: msg,
+            })
+        }
+    }
+
+    /// Send a message with the given priority on the channel.
+    ///
+    /// This function should only be called after
+    /// [`poll_ready`](Sender::poll_ready) has reported that the channel is
+    /// ready to receive a message.
+    pub fn start_send(&mut self, priority: P, msg: T) -> Result<(), SendError> {
+        self.try_send(priority, msg)
+            .map_err(|e| e.err)
+    }
+}
+
+impl<P, T> Sender<P, T> {
+    /// Polls the channel to determine if there is guaranteed capacity to send
+    /// at least one item without waiting.
+    ///
+    /// # Return value
+    ///
+    /// This method returns:
+    ///
+    /// - `Ok(Poll::Ready(_))` if there is sufficient capacity;
+    /// - `Ok(Poll::Pending)` if the channel may not have
+    ///   capacity, in which case the current task is queued to be notified once
+    ///   capacity is available;
+    /// - `Err(SendError)` if the receiver has been dropped.
+    pub fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), SendError>> {
+        let inner = self.0.as_mut().ok_or_else(disconnected)?;
+        inner.poll_ready(cx)
+    }
+
+    /// Returns whether this channel is closed without needing a context.
+    pub fn is_closed(&self) -> bool {
+        self.0.as_ref().map(|inner| !inner.shared.lock().open).unwrap_or(true)
+    }
+
+    /// Closes this channel from the sender side, preventing any new messages.
+    pub fn close_channel(&mut self) {
+        if let Some(inner) = &self.0 {
+            inner.shared.lock().close();
+        }
+    }
+
+    /// Disconnects this sender from the channel, ending the receiver's
+    /// stream if there are no more senders left.
+    pub fn disconnect(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl<P, T> Clone for Sender<P, T> {
+    fn clone(&self) -> Sender<P, T> {
+        Sender(self.0.clone())
+    }
+}
+
+impl<P: Ord, T> Receiver<P, T> {
+    /// Closes the receiving half of the channel, without dropping it.
+    ///
+    /// This prevents any further messages from being sent on the channel
+    /// while still enabling the receiver to drain messages that are
+    /// buffered.
+    pub fn close(&mut self) {
+        self.shared.lock().close();
+    }
+
+    /// Tries to receive the highest-priority message without notifying a
+    /// context if empty.
+    ///
+    /// It is not recommended to call this function from inside of a future,
+    /// only when you've otherwise arranged to be notified when the channel is
+    /// no longer empty.
+    pub fn try_next(&mut self) -> Result<Option<T>, TryRecvError> {
+        match self.next_message(None) {
+            Poll::Ready(msg) => Ok(msg),
+            Poll::Pending => Err(TryRecvError { _inner: () }),
+        }
+    }
+
+    fn next_message(&mut self, cx: Option<&mut Context<'_>>) -> Poll<Option<T>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let mut state = self.shared.lock();
+        if let Some(entry) = state.queue.pop() {
+            // A slot was freed, so let the sender that has been waiting the
+            // longest send again.
+            if let Some(task) = state.parked.pop_front() {
+                task.lock().unwrap().notify();
+            }
+            Poll::Ready(Some(entry.msg))
+        } else if !state.open || state.num_senders == 0 {
+            self.terminated = true;
+            Poll::Ready(None)
+        } else {
+            if let Some(cx) = cx {
+                state.recv_task = Some(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<P: Ord, T> Stream for Receiver<P, T> {
+    type Item = T;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<T>> {
+        self.next_message(Some(cx))
+    }
+}
+
+impl<P: Ord, T> FusedStream for Receiver<P, T> {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl<P, T> Drop for Receiver<P, T> {
+    fn drop(&mut self) {
+        let remaining = {
+            let mut state = self.shared.lock();
+            state.close();
+            state.queue.drain().collect::<Vec<_>>()
+        };
+        // Drop the buffered messages outside of the lock, as they might hold
+        // senders of this very channel.
+        drop(remaining);
+    }
+}
+
+// Higher priorities first, then earlier messages first
+impl<P: Ord, T> Ord for Entry<P, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<P: Ord, T> PartialOrd for Entry<P, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P: Ord, T> PartialEq for Entry<P, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<P: Ord, T> Eq for Entry<P, T> {}
+
+impl<P: fmt::Debug, T> fmt::Debug for Entry<P, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("priority", &self.priority)
+            .field("seq", &self.seq)
+            .finish()
+    }
+}
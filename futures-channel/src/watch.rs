@@ -0,0 +1,273 @@
+//! A single-producer, multi-consumer channel which only retains the most
+//! recently sent value.
+//!
+//! This is useful for propagating state, such as the current configuration,
+//! where readers only care about the latest value. Channel creation provides a
+//! [`Sender`](Sender) and a [`Receiver`](Receiver), given the initial value.
+//! [`Sender::broadcast`](Sender::broadcast) replaces the value and wakes every
+//! receiver. [`Receiver::borrow`](Receiver::borrow) gives access to the
+//! current value at any time, and [`Receiver`](Receiver) implements
+//! [`Stream`](futures_core::stream::Stream), yielding the value whenever it
+//! changed since the receiver last saw it. Intermediate values may be skipped
+//! if several are sent in between.
+//!
+//! Receivers can be cloned; a clone starts out having seen the same values
+//! as the receiver it was cloned from.
+//!
+//! # Disconnection
+//!
+//! When the [`Sender`](Sender) is dropped, receivers still get the last value
+//! if they have not seen it yet, after which their stream ends. When all
+//! [`Receiver`](Receiver) handles have been dropped, broadcasting fails.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::channel::watch;
+//! use futures::executor::block_on_stream;
+//!
+//! let (tx, rx) = watch::channel("initial");
+//! let mut values = block_on_stream(rx.clone());
+//! assert_eq!(values.next(), Some("initial"));
+//!
+//! tx.broadcast("first").unwrap();
+//! tx.broadcast("second").unwrap();
+//! assert_eq!(*rx.borrow(), "second");
+//!
+//! // only the latest value is yielded
+//! assert_eq!(values.next(), Some("second"));
+//! drop(tx);
+//! assert_eq!(values.next(), None);
+//! ```
+
+use futures_core::stream::{FusedStream, Stream};
+use futures_core::task::{Context, Poll, Waker};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
+/// The sending half of a watch channel.
+///
+/// This is created by the [`channel`](channel) function.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of a watch channel.
+///
+/// This is created by the [`channel`](channel) function, and can be cloned to
+/// get more receivers.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // Identifies this receiver's waker in `State::wakers`
+    id: usize,
+    // Version of the last value this receiver has seen
+    seen: u64,
+    terminated: bool,
+}
+
+/// A reference to the current value of a watch channel, returned by
+/// [`Receiver::borrow`](Receiver::borrow).
+///
+/// The sender cannot replace the value while this reference is alive.
+#[derive(Debug)]
+pub struct Ref<'a, T> {
+    guard: RwLockReadGuard<'a, Versioned<T>>,
+}
+
+// The channel does not ever project Pin to the inner T
+impl<T> Unpin for Sender<T> {}
+impl<T> Unpin for Receiver<T> {}
+
+#[derive(Debug)]
+struct Shared<T> {
+    value: RwLock<Versioned<T>>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct Versioned<T> {
+    value: T,
+    // Incremented whenever the value is replaced
+    version: u64,
+}
+
+#[derive(Debug)]
+struct State {
+    closed: bool,
+    num_receivers: usize,
+    // Receivers waiting for a new value, by id
+    wakers: HashMap<usize, Waker>,
+    next_id: usize,
+}
+
+/// The error returned by [`Sender::broadcast`](Sender::broadcast) when there
+/// are no receivers left. Contains the value that failed to be sent.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// Creates a new watch channel holding `init`, returning the sender and a
+/// first receiver.
+///
+/// The receiver has not seen the initial value yet, so its stream yields it
+/// right away.
+pub fn channel<T: Clone>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(Versioned { value: init, version: 1 }),
+        state: Mutex::new(State {
+            closed: false,
+            num_receivers: 0,
+            wakers: HashMap::new(),
+            next_id: 0,
+        }),
+    });
+    let rx = Receiver::new(shared.clone(), 0);
+    (Sender { shared }, rx)
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl State {
+    fn wake_all(&mut self) {
+        for (_, waker) in self.wakers.drain() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Replaces the value of the channel, and notifies every receiver.
+    ///
+    /// Returns an error containing the value if there are no receivers.
+    pub fn broadcast(&self, value: T) -> Result<(), SendError<T>> {
+        if self.shared.lock().num_receivers == 0 {
+            return Err(SendError(value));
+        }
+        {
+            let mut current = self.shared.value.write().unwrap();
+            current.value = value;
+            current.version += 1;
+        }
+        self.shared.lock().wake_all();
+        Ok(())
+    }
+
+    /// Returns the number of receivers currently alive.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().num_receivers
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        state.wake_all();
+    }
+}
+
+impl<T> Receiver<T> {
+    fn new(shared: Arc<Shared<T>>, seen: u64) -> Receiver<T> {
+        let id = {
+            let mut state = shared.lock();
+            state.num_receivers += 1;
+            state.next_id += 1;
+            state.next_id
+        };
+        Receiver { shared, id, seen, terminated: false }
+    }
+
+    /// Returns a reference to the current value.
+    ///
+    /// This does not mark the value as seen.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref { guard: self.shared.value.read().unwrap() }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        Receiver::new(self.shared.clone(), self.seen)
+    }
+}
+
+impl<T: Clone> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<T>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let this = &mut *self;
+        // Register for wakeups before looking at the value, so that a value
+        // broadcast in between cannot be missed.
+        let mut state = this.shared.lock();
+        state.wakers.insert(this.id, cx.waker().clone());
+        {
+            let current = this.shared.value.read().unwrap();
+            if current.version != this.seen {
+                this.seen = current.version;
+                return Poll::Ready(Some(current.value.clone()));
+            }
+        }
+        if state.closed {
+            this.terminated = true;
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T: Clone> FusedStream for Receiver<T> {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.num_receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard.value
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("SendError")
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "send failed because there are no receivers")
+    }
+}
+
+impl<T> Error for SendError<T> {
+    fn description(&self) -> &str {
+        "send failed because there are no receivers"
+    }
+}
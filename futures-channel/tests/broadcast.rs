@@ -0,0 +1,89 @@
+#![feature(futures_api)]
+
+use futures::channel::broadcast::{self, RecvError, TryRecvError};
+use futures::executor::{block_on, block_on_stream};
+use futures::stream::StreamExt;
+use std::thread;
+use std::time::Duration;
+
+trait AssertSend: Send {}
+impl AssertSend for broadcast::Sender<i32> {}
+impl AssertSend for broadcast::Receiver<i32> {}
+
+#[test]
+fn every_receiver_sees_every_value() {
+    let (tx, rx1) = broadcast::channel(4);
+    let rx2 = tx.subscribe();
+    assert_eq!(tx.receiver_count(), 2);
+
+    let t = thread::spawn(move || {
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+    });
+
+    let got1: Vec<_> = block_on_stream(rx1).map(Result::unwrap).collect();
+    let got2: Vec<_> = block_on_stream(rx2).map(Result::unwrap).collect();
+    assert_eq!(got1, vec![0, 1, 2]);
+    assert_eq!(got2, vec![0, 1, 2]);
+    t.join().unwrap();
+}
+
+#[test]
+fn subscribers_only_see_later_values() {
+    let (tx, mut rx1) = broadcast::channel(4);
+    tx.send(1).unwrap();
+    let mut rx2 = tx.subscribe();
+    tx.send(2).unwrap();
+
+    assert_eq!(rx1.try_recv(), Ok(1));
+    assert_eq!(rx1.try_recv(), Ok(2));
+    assert_eq!(rx2.try_recv(), Ok(2));
+    assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+
+    drop(tx);
+    assert_eq!(rx1.try_recv(), Err(TryRecvError::Closed));
+}
+
+#[test]
+fn slow_receiver_lags() {
+    let (tx, rx) = broadcast::channel(2);
+    for i in 0..5 {
+        tx.send(i).unwrap();
+    }
+    drop(tx);
+
+    let got: Vec<_> = block_on_stream(rx).collect();
+    assert_eq!(got, vec![Err(RecvError::Lagged(3)), Ok(3), Ok(4)]);
+}
+
+#[test]
+fn overwritten_value_may_own_a_sender() {
+    #[derive(Clone)]
+    struct Msg(Option<broadcast::Sender<Msg>>);
+
+    let (tx, rx) = broadcast::channel(1);
+    assert!(tx.send(Msg(Some(tx.clone()))).is_ok());
+    // dropping the overwritten value drops its sender
+    assert!(tx.send(Msg(None)).is_ok());
+    drop(tx);
+
+    let got: Vec<_> = block_on_stream(rx).map(|msg| msg.map(|Msg(tx)| tx.is_some())).collect();
+    assert_eq!(got, vec![Err(RecvError::Lagged(1)), Ok(false)]);
+}
+
+#[test]
+fn send_without_receivers_fails() {
+    let (tx, rx) = broadcast::channel(2);
+    drop(rx);
+    assert_eq!(tx.send(1).unwrap_err().0, 1);
+}
+
+#[test]
+fn pending_receiver_is_woken() {
+    let (tx, mut rx) = broadcast::channel(2);
+    let t = thread::spawn(move || block_on(rx.next()));
+    thread::sleep(Duration::from_millis(50));
+    tx.send(7).unwrap();
+    assert_eq!(t.join().unwrap(), Some(Ok(7)));
+}
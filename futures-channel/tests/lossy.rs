@@ -0,0 +1,65 @@
+#![feature(futures_api)]
+
+use futures::channel::lossy::{self, Overflow};
+use futures::executor::{block_on, block_on_stream};
+use futures::stream::StreamExt;
+use futures::task::{Context, Poll};
+use futures_test::task::noop_waker_ref;
+use std::thread;
+use std::time::Duration;
+
+trait AssertSend: Send {}
+impl AssertSend for lossy::Sender<i32> {}
+impl AssertSend for lossy::Receiver<i32> {}
+
+#[test]
+fn drop_oldest() {
+    let (tx, rx) = lossy::channel(3, Overflow::DropOldest);
+    for i in 0..5 {
+        tx.try_send(i).unwrap();
+    }
+    assert_eq!(rx.dropped_count(), 2);
+    drop(tx);
+    assert_eq!(block_on_stream(rx).collect::<Vec<_>>(), vec![2, 3, 4]);
+}
+
+#[test]
+fn drop_newest() {
+    let (tx, rx) = lossy::channel(3, Overflow::DropNewest);
+    for i in 0..5 {
+        tx.try_send(i).unwrap();
+    }
+    drop(tx);
+    let mut rx = block_on_stream(rx);
+    assert_eq!(rx.by_ref().collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(rx.into_inner().dropped_count(), 2);
+}
+
+#[test]
+fn send_fails_once_closed() {
+    let (tx, mut rx) = lossy::channel(2, Overflow::DropOldest);
+    tx.try_send(1).unwrap();
+    rx.close();
+    assert!(tx.is_closed());
+    assert_eq!(tx.try_send(2).unwrap_err().0, 2);
+
+    // the buffered message can still be received
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+    assert_eq!(rx.try_next().unwrap(), None);
+
+    drop(rx);
+    assert!(tx.try_send(3).is_err());
+}
+
+#[test]
+fn pending_receiver_is_woken() {
+    let (tx, mut rx) = lossy::channel(2, Overflow::DropOldest);
+    let cx = &mut Context::from_waker(noop_waker_ref());
+    assert_eq!(rx.poll_next_unpin(cx), Poll::Pending);
+    assert!(rx.try_next().is_err());
+
+    let t = thread::spawn(move || block_on(rx.next()));
+    thread::sleep(Duration::from_millis(50));
+    tx.try_send(1).unwrap();
+    assert_eq!(t.join().unwrap(), Some(1));
+}
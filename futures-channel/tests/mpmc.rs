@@ -0,0 +1,100 @@
+#![feature(futures_api)]
+
+use futures::channel::mpmc;
+use futures::executor::{block_on, block_on_stream};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use futures::task::{Context, Poll};
+use futures_test::task::noop_waker_ref;
+use std::thread;
+
+trait AssertSend: Send {}
+impl AssertSend for mpmc::Sender<i32> {}
+impl AssertSend for mpmc::Receiver<i32> {}
+
+#[test]
+fn each_message_received_once() {
+    let (tx, rx) = mpmc::channel(4);
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let rx = rx.clone();
+            thread::spawn(move || block_on_stream(rx).collect::<Vec<i32>>())
+        })
+        .collect();
+    drop(rx);
+
+    let producers: Vec<_> = (0..2)
+        .map(|p| {
+            let mut tx = tx.clone();
+            thread::spawn(move || {
+                for i in 0..500 {
+                    block_on(tx.send(p * 500 + i)).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+    for p in producers {
+        p.join().unwrap();
+    }
+
+    let mut got: Vec<i32> = workers.into_iter()
+        .flat_map(|w| w.join().unwrap())
+        .collect();
+    got.sort();
+    assert_eq!(got, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn backpressure() {
+    let (mut tx, mut rx) = mpmc::channel(1);
+    let cx = &mut Context::from_waker(noop_waker_ref());
+
+    // one buffered slot, plus the sender's guaranteed slot
+    tx.try_send(1).unwrap();
+    tx.try_send(2).unwrap();
+    assert!(tx.try_send(3).unwrap_err().is_full());
+    assert!(tx.poll_ready(cx).is_pending());
+
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+    assert_eq!(tx.poll_ready(cx), Poll::Ready(Ok(())));
+    tx.try_send(3).unwrap();
+}
+
+#[test]
+fn close_from_any_receiver() {
+    let (mut tx, mut rx1) = mpmc::channel(4);
+    let rx2 = rx1.clone();
+    tx.try_send(1).unwrap();
+
+    rx1.close();
+    assert!(tx.is_closed());
+    assert!(tx.try_send(2).unwrap_err().is_disconnected());
+
+    // the buffered message can still be drained, by either receiver
+    assert_eq!(block_on_stream(rx2).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(rx1.try_next().unwrap(), None);
+}
+
+#[test]
+fn closed_when_all_receivers_dropped() {
+    let (mut tx, rx1) = mpmc::channel::<i32>(4);
+    let rx2 = rx1.clone();
+
+    drop(rx1);
+    assert!(!tx.is_closed());
+    drop(rx2);
+    assert!(tx.is_closed());
+    assert!(tx.try_send(1).unwrap_err().is_disconnected());
+}
+
+#[test]
+fn parked_sender_woken_on_close() {
+    let (mut tx, rx) = mpmc::channel(0);
+    tx.try_send(1).unwrap();
+
+    let t = thread::spawn(move || block_on(tx.send(2)));
+    drop(rx);
+    assert!(t.join().unwrap().unwrap_err().is_disconnected());
+}
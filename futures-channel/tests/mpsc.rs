@@ -2,11 +2,11 @@
 
 use futures::channel::{mpsc, oneshot};
 use futures::executor::{block_on, block_on_stream};
-use futures::future::{join, poll_fn};
+use futures::future::{join, poll_fn, FutureExt};
 use futures::stream::{Stream, StreamExt};
 use futures::sink::{Sink, SinkExt};
 use futures::task::{Context, Poll};
-use futures_test::task::noop_waker_ref;
+use futures_test::task::{new_count_waker, noop_waker_ref};
 use pin_utils::pin_mut;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -521,3 +521,203 @@ fn try_send_recv() {
     rx.try_next().unwrap();
     rx.try_next().unwrap_err(); // should be empty
 }
+
+#[test]
+fn reserve_permit() {
+    let (mut tx, mut rx) = mpsc::channel(0);
+    let cx = &mut Context::from_waker(noop_waker_ref());
+
+    block_on(tx.reserve()).unwrap().send(1);
+
+    // the sender's slot is taken until the message is received
+    assert!(tx.reserve().poll_unpin(cx).is_pending());
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+
+    // dropping a permit unused leaves the slot available
+    drop(block_on(tx.reserve()).unwrap());
+    block_on(tx.reserve()).unwrap().send(2);
+    assert_eq!(rx.try_next().unwrap(), Some(2));
+
+    drop(rx);
+    assert!(block_on(tx.reserve()).unwrap_err().is_disconnected());
+}
+
+#[test]
+fn reserve_owned_permit() {
+    let (tx, mut rx) = mpsc::channel(0);
+
+    let permit = block_on(tx.reserve_owned()).unwrap();
+    let tx = permit.send(1);
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+
+    let tx = block_on(tx.reserve_owned()).unwrap().release();
+    drop(tx);
+    assert_eq!(rx.try_next().unwrap(), None);
+}
+
+#[test]
+fn recv_many() {
+    let (mut tx, mut rx) = mpsc::channel(10);
+    for i in 0..5 {
+        tx.try_send(i).unwrap();
+    }
+
+    let mut buf = Vec::new();
+    assert_eq!(block_on(rx.recv_many(&mut buf, 3)), 3);
+    assert_eq!(block_on(rx.recv_many(&mut buf, 3)), 2);
+    assert_eq!(buf, vec![0, 1, 2, 3, 4]);
+
+    let cx = &mut Context::from_waker(noop_waker_ref());
+    assert_eq!(rx.poll_recv_many(cx, &mut buf, 3), Poll::Pending);
+    drop(tx);
+    assert_eq!(rx.poll_recv_many(cx, &mut buf, 3), Poll::Ready(0));
+}
+
+#[test]
+fn recv_many_unparks_senders() {
+    let (tx, mut rx) = mpsc::channel(0);
+    let mut txs: Vec<_> = (0..3).map(|_| tx.clone()).collect();
+    for (i, tx) in txs.iter_mut().enumerate() {
+        tx.try_send(i).unwrap();
+        assert!(tx.try_send(i).unwrap_err().is_full());
+    }
+
+    let mut buf = Vec::new();
+    assert_eq!(block_on(rx.recv_many(&mut buf, 10)), 3);
+    for tx in &mut txs {
+        tx.try_send(0).unwrap();
+    }
+}
+
+#[test]
+fn send_batch() {
+    let (mut tx, mut rx) = mpsc::channel(1);
+
+    // the buffer and the sender's slot are filled, the rest is handed back
+    let err = tx.try_send_batch(vec![1, 2, 3]).unwrap_err();
+    assert!(err.is_full());
+    assert_eq!(err.into_inner(), vec![3]);
+    assert!(tx.try_send_batch(vec![3]).unwrap_err().is_full());
+
+    let mut buf = Vec::new();
+    assert_eq!(block_on(rx.recv_many(&mut buf, 10)), 2);
+    tx.try_send_batch(vec![3, 4]).unwrap();
+
+    // `send_batch` waits for capacity as many times as needed
+    let t = thread::spawn(move || block_on(tx.send_batch(vec![5, 6, 7, 8])).unwrap());
+    while block_on(rx.recv_many(&mut buf, 10)) > 0 {}
+    t.join().unwrap();
+    assert_eq!(buf, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let (tx, mut rx) = mpsc::unbounded();
+    tx.unbounded_send_batch(vec![1, 2]).unwrap();
+    drop(tx);
+    assert_eq!(block_on(rx.collect::<Vec<_>>()), vec![1, 2]);
+}
+
+#[test]
+fn send_empty_batch_after_close() {
+    let (mut tx, rx) = mpsc::channel::<i32>(1);
+    tx.try_send_batch(Vec::new()).unwrap();
+    drop(rx);
+    assert!(tx.try_send_batch(Vec::new()).unwrap_err().is_disconnected());
+
+    let (tx, rx) = mpsc::unbounded::<i32>();
+    tx.unbounded_send_batch(Vec::new()).unwrap();
+    drop(rx);
+    assert!(tx.unbounded_send_batch(Vec::new()).unwrap_err().is_disconnected());
+}
+
+#[test]
+fn introspection() {
+    let (mut tx, mut rx) = mpsc::channel(4);
+    assert_eq!(tx.capacity(), 4);
+    assert_eq!(rx.capacity(), 4);
+    assert!(tx.is_empty() && rx.is_empty());
+
+    tx.try_send(1).unwrap();
+    tx.try_send(2).unwrap();
+    assert_eq!(tx.len(), 2);
+    assert_eq!(rx.len(), 2);
+    rx.try_next().unwrap();
+    assert_eq!(rx.len(), 1);
+
+    let tx2 = tx.clone();
+    assert_eq!(rx.sender_count(), 2);
+    assert!(tx.same_receiver(&tx2));
+    assert!(!tx.same_receiver(&mpsc::channel(4).0));
+
+    tx.disconnect();
+    assert_eq!(tx.sender_count(), 0);
+    assert!(!tx.same_receiver(&tx2));
+    assert_eq!(tx2.sender_count(), 1);
+
+    let (tx, rx) = mpsc::unbounded();
+    tx.unbounded_send(1).unwrap();
+    assert_eq!(rx.len(), 1);
+    assert_eq!(tx.capacity(), None);
+    assert_eq!(rx.capacity(), None);
+    assert_eq!(tx.sender_count(), 1);
+    assert!(tx.same_receiver(&tx.clone()));
+}
+
+#[test]
+fn weak_sender() {
+    let (tx, mut rx) = mpsc::channel(4);
+    let weak = tx.downgrade();
+    assert_eq!(rx.sender_count(), 1);
+
+    let mut tx2 = weak.upgrade().unwrap();
+    assert_eq!(rx.sender_count(), 2);
+    tx2.try_send(1).unwrap();
+    drop(tx2);
+    drop(tx);
+
+    // the weak sender did not keep the channel open
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+    assert_eq!(rx.try_next().unwrap(), None);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn weak_unbounded_sender() {
+    let (tx, rx) = mpsc::unbounded();
+    let weak = tx.downgrade();
+    weak.upgrade().unwrap().unbounded_send(1).unwrap();
+    drop(tx);
+
+    assert!(weak.clone().upgrade().is_none());
+    assert_eq!(block_on(rx.collect::<Vec<_>>()), vec![1]);
+}
+
+#[test]
+fn sender_closed() {
+    let (tx, mut rx) = mpsc::channel::<i32>(1);
+    let cx = &mut Context::from_waker(noop_waker_ref());
+    assert_eq!(tx.poll_closed(cx), Poll::Pending);
+
+    let t = thread::spawn(move || block_on(tx.closed()));
+    rx.close();
+    t.join().unwrap();
+
+    let (tx, rx) = mpsc::unbounded::<i32>();
+    let mut closed = tx.closed();
+    assert_eq!(closed.poll_unpin(cx), Poll::Pending);
+    drop(rx);
+    assert_eq!(closed.poll_unpin(cx), Poll::Ready(()));
+}
+
+#[test]
+fn sender_closed_from_two_tasks() {
+    let (tx, mut rx) = mpsc::channel::<i32>(1);
+    let (waker1, count1) = new_count_waker();
+    let (waker2, count2) = new_count_waker();
+    let mut closed1 = tx.closed();
+    let mut closed2 = tx.closed();
+    assert_eq!(closed1.poll_unpin(&mut Context::from_waker(&waker1)), Poll::Pending);
+    assert_eq!(closed2.poll_unpin(&mut Context::from_waker(&waker2)), Poll::Pending);
+
+    rx.close();
+    assert_eq!(count1, 1);
+    assert_eq!(count2, 1);
+}
@@ -0,0 +1,72 @@
+#![feature(futures_api)]
+
+use futures::channel::priority;
+use futures::executor::{block_on, block_on_stream};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use futures::task::{Context, Poll};
+use futures_test::task::noop_waker_ref;
+use std::thread;
+
+trait AssertSend: Send {}
+impl AssertSend for priority::Sender<u8, i32> {}
+impl AssertSend for priority::Receiver<u8, i32> {}
+
+#[test]
+fn highest_priority_first() {
+    let (mut tx, rx) = priority::channel(10);
+    tx.try_send(1, "a").unwrap();
+    tx.try_send(3, "b").unwrap();
+    tx.try_send(2, "c").unwrap();
+    tx.try_send(3, "d").unwrap();
+    tx.try_send(1, "e").unwrap();
+    drop(tx);
+
+    // equal priorities keep their order
+    assert_eq!(block_on_stream(rx).collect::<Vec<_>>(), vec!["b", "d", "c", "a", "e"]);
+}
+
+#[test]
+fn backpressure() {
+    let (mut tx, mut rx) = priority::channel(1);
+    let cx = &mut Context::from_waker(noop_waker_ref());
+
+    // one buffered slot, plus the sender's guaranteed slot
+    tx.try_send(0, 1).unwrap();
+    tx.try_send(0, 2).unwrap();
+    assert!(tx.try_send(9, 3).unwrap_err().is_full());
+    assert!(tx.poll_ready(cx).is_pending());
+
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+    assert_eq!(tx.poll_ready(cx), Poll::Ready(Ok(())));
+    tx.try_send(9, 3).unwrap();
+    assert_eq!(rx.try_next().unwrap(), Some(3));
+}
+
+#[test]
+fn sink_of_pairs() {
+    let (tx, rx) = priority::channel(0);
+
+    let t = thread::spawn(move || {
+        let mut tx = tx;
+        for i in 0..10 {
+            block_on(tx.send((i % 2, i))).unwrap();
+        }
+    });
+    let mut got: Vec<i32> = block_on_stream(rx).collect();
+    t.join().unwrap();
+    got.sort();
+    assert_eq!(got, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn close() {
+    let (mut tx, mut rx) = priority::channel(4);
+    tx.try_send(0, 1).unwrap();
+    rx.close();
+    assert!(tx.is_closed());
+    assert!(tx.try_send(0, 2).unwrap_err().is_disconnected());
+    assert_eq!(rx.try_next().unwrap(), Some(1));
+    assert_eq!(rx.try_next().unwrap(), None);
+    assert_eq!(block_on(rx.next()), None);
+}
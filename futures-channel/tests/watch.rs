@@ -0,0 +1,66 @@
+#![feature(futures_api)]
+
+use futures::channel::watch;
+use futures::executor::{block_on, block_on_stream};
+use futures::stream::StreamExt;
+use futures::task::{Context, Poll};
+use futures_test::task::noop_waker_ref;
+use std::thread;
+use std::time::Duration;
+
+trait AssertSend: Send {}
+impl AssertSend for watch::Sender<i32> {}
+impl AssertSend for watch::Receiver<i32> {}
+
+#[test]
+fn borrow_sees_latest_value() {
+    let (tx, rx) = watch::channel(0);
+    assert_eq!(*rx.borrow(), 0);
+    tx.broadcast(1).unwrap();
+    assert_eq!(*rx.borrow(), 1);
+}
+
+#[test]
+fn stream_yields_unseen_changes_only() {
+    let (tx, mut rx) = watch::channel(0);
+    let cx = &mut Context::from_waker(noop_waker_ref());
+
+    assert_eq!(rx.poll_next_unpin(cx), Poll::Ready(Some(0)));
+    assert!(rx.poll_next_unpin(cx).is_pending());
+
+    tx.broadcast(1).unwrap();
+    tx.broadcast(2).unwrap();
+    assert_eq!(rx.poll_next_unpin(cx), Poll::Ready(Some(2)));
+    assert!(rx.poll_next_unpin(cx).is_pending());
+
+    // a clone has seen the same values as the original
+    let mut rx2 = rx.clone();
+    assert!(rx2.poll_next_unpin(cx).is_pending());
+    assert_eq!(tx.receiver_count(), 2);
+}
+
+#[test]
+fn stream_ends_after_last_value() {
+    let (tx, rx) = watch::channel(0);
+    tx.broadcast(1).unwrap();
+    drop(tx);
+    assert_eq!(block_on_stream(rx).collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+fn broadcast_without_receivers_fails() {
+    let (tx, rx) = watch::channel(0);
+    drop(rx);
+    assert_eq!(tx.broadcast(1).unwrap_err().0, 1);
+}
+
+#[test]
+fn pending_receiver_is_woken() {
+    let (tx, mut rx) = watch::channel(0);
+    assert_eq!(block_on(rx.next()), Some(0));
+
+    let t = thread::spawn(move || block_on(rx.next()));
+    thread::sleep(Duration::from_millis(50));
+    tx.broadcast(1).unwrap();
+    assert_eq!(t.join().unwrap(), Some(1));
+}
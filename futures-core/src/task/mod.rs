@@ -3,6 +3,6 @@
 mod spawn;
 #[doc(hidden)]
 pub mod __internal;
-pub use self::spawn::{Spawn, LocalSpawn, SpawnError};
+pub use self::spawn::{Spawn, LocalSpawn, SpawnError, TaskOptions};
 
 pub use core::task::{Context, Poll, Waker, RawWaker, RawWakerVTable};
@@ -15,6 +15,26 @@ pub trait Spawn {
     fn spawn_obj(&mut self, future: FutureObj<'static, ()>)
         -> Result<(), SpawnError>;
 
+    /// Spawns a future that will be run to completion, described by the
+    /// given [`TaskOptions`](TaskOptions).
+    ///
+    /// Executors may use the options, for example to attach the task's name
+    /// to diagnostics. The default implementation ignores them and calls
+    /// [`spawn_obj`](Spawn::spawn_obj).
+    ///
+    /// # Errors
+    ///
+    /// See [`spawn_obj`](Spawn::spawn_obj).
+    #[inline]
+    fn spawn_obj_with(
+        &mut self,
+        future: FutureObj<'static, ()>,
+        options: &TaskOptions<'_>,
+    ) -> Result<(), SpawnError> {
+        let _ = options;
+        self.spawn_obj(future)
+    }
+
     /// Determines whether the executor is able to spawn new tasks.
     ///
     /// This method will return `Ok` when the executor is *likely*
@@ -41,6 +61,26 @@ pub trait LocalSpawn {
     fn spawn_local_obj(&mut self, future: LocalFutureObj<'static, ()>)
         -> Result<(), SpawnError>;
 
+    /// Spawns a future that will be run to completion, described by the
+    /// given [`TaskOptions`](TaskOptions).
+    ///
+    /// Executors may use the options, for example to attach the task's name
+    /// to diagnostics. The default implementation ignores them and calls
+    /// [`spawn_local_obj`](LocalSpawn::spawn_local_obj).
+    ///
+    /// # Errors
+    ///
+    /// See [`spawn_local_obj`](LocalSpawn::spawn_local_obj).
+    #[inline]
+    fn spawn_local_obj_with(
+        &mut self,
+        future: LocalFutureObj<'static, ()>,
+        options: &TaskOptions<'_>,
+    ) -> Result<(), SpawnError> {
+        let _ = options;
+        self.spawn_local_obj(future)
+    }
+
     /// Determines whether the executor is able to spawn new tasks.
     ///
     /// This method will return `Ok` when the executor is *likely*
@@ -53,6 +93,35 @@ pub trait LocalSpawn {
     }
 }
 
+/// Optional information about a task, passed to
+/// [`Spawn::spawn_obj_with`](Spawn::spawn_obj_with) and
+/// [`LocalSpawn::spawn_local_obj_with`](LocalSpawn::spawn_local_obj_with).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TaskOptions<'a> {
+    name: Option<&'a str>,
+}
+
+impl<'a> TaskOptions<'a> {
+    /// Creates options which don't describe the task at all.
+    pub fn new() -> Self {
+        Self { name: None }
+    }
+
+    /// Sets the name of the task.
+    ///
+    /// Names are meant for humans: they need not be unique, and executors
+    /// typically show them in panic messages and debug output.
+    pub fn with_name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Returns the name of the task, if one was set.
+    pub fn name(&self) -> Option<&'a str> {
+        self.name
+    }
+}
+
 /// An error that occurred during spawning.
 pub struct SpawnError {
     _hidden: (),
@@ -89,6 +158,11 @@ mod if_alloc {
             (**self).spawn_obj(future)
         }
 
+        fn spawn_obj_with(&mut self, future: FutureObj<'static, ()>, options: &TaskOptions<'_>)
+        -> Result<(), SpawnError> {
+            (**self).spawn_obj_with(future, options)
+        }
+
         fn status(&self) -> Result<(), SpawnError> {
             (**self).status()
         }
@@ -100,6 +174,11 @@ mod if_alloc {
             (**self).spawn_local_obj(future)
         }
 
+        fn spawn_local_obj_with(&mut self, future: LocalFutureObj<'static, ()>, options: &TaskOptions<'_>)
+        -> Result<(), SpawnError> {
+            (**self).spawn_local_obj_with(future, options)
+        }
+
         fn status_local(&self) -> Result<(), SpawnError> {
             (**self).status_local()
         }
@@ -0,0 +1,185 @@
+use futures_channel::oneshot::{self, Receiver};
+use futures_core::future::Future;
+use futures_core::task::{Context, Poll};
+use futures_util::future::FutureExt;
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::prelude::v1::*;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A future which resolves to the return value of a closure run by
+/// [`ThreadPool::spawn_blocking`](crate::ThreadPool::spawn_blocking).
+///
+/// If the closure panics, the panic is propagated to the task polling this
+/// future.
+#[must_use = "futures do nothing unless polled"]
+pub struct SpawnBlocking<T> {
+    rx: Receiver<thread::Result<T>>,
+}
+
+impl<T> Unpin for SpawnBlocking<T> {}
+
+impl<T> Future for SpawnBlocking<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match self.rx.poll_unpin(cx) {
+            Poll::Ready(Ok(Ok(output))) => Poll::Ready(output),
+            Poll::Ready(Ok(Err(payload))) => panic::resume_unwind(payload),
+            Poll::Ready(Err(_)) => panic!("blocking closure was dropped before running"),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> fmt::Debug for SpawnBlocking<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpawnBlocking").finish()
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// An elastic pool of threads for running blocking closures.
+///
+/// Threads are spawned on demand, up to `max_threads`, and exit after having
+/// been idle for `keep_alive`.
+pub(crate) struct BlockingPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    condvar: Condvar,
+    max_threads: usize,
+    keep_alive: Duration,
+    stack_size: usize,
+    name_prefix: Option<String>,
+}
+
+struct State {
+    queue: VecDeque<Job>,
+    num_threads: usize,
+    num_idle: usize,
+    // Number of idle threads which have been notified of a new job but
+    // haven't woken up yet
+    num_notified: usize,
+    // Used to give spawned threads unique names
+    next_id: usize,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(
+        max_threads: usize,
+        keep_alive: Duration,
+        stack_size: usize,
+        name_prefix: Option<String>,
+    ) -> BlockingPool {
+        BlockingPool {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    queue: VecDeque::new(),
+                    num_threads: 0,
+                    num_idle: 0,
+                    num_notified: 0,
+                    next_id: 0,
+                }),
+                condvar: Condvar::new(),
+                max_threads,
+                keep_alive,
+                stack_size,
+                name_prefix,
+            }),
+        }
+    }
+
+    pub(crate) fn spawn<F, T>(&self, f: F) -> SpawnBlocking<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.push(Box::new(move || {
+            // AssertUnwindSafe is used here because `Send + 'static` is
+            // basically an alias for an implementation of the `UnwindSafe`
+            // trait but we can't express that in the standard library right
+            // now.
+            let res = panic::catch_unwind(AssertUnwindSafe(f));
+            // if the receiving end has gone away then that's ok, we just
+            // ignore the send error here.
+            drop(tx.send(res));
+        }));
+        SpawnBlocking { rx }
+    }
+
+    fn push(&self, job: Job) {
+        let id = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.queue.push_back(job);
+            if state.num_idle > state.num_notified {
+                // Wake an idle thread which hasn't already been told about
+                // another job
+                state.num_notified += 1;
+                self.inner.condvar.notify_one();
+                return;
+            }
+            if state.num_threads == self.inner.max_threads {
+                // the job will be picked up once a thread frees up
+                return;
+            }
+            state.num_threads += 1;
+            state.next_id += 1;
+            state.next_id
+        };
+
+        let mut thread_builder = thread::Builder::new();
+        if let Some(ref name_prefix) = self.inner.name_prefix {
+            thread_builder = thread_builder.name(format!("{}blocking-{}", name_prefix, id));
+        }
+        if self.inner.stack_size > 0 {
+            thread_builder = thread_builder.stack_size(self.inner.stack_size);
+        }
+        let inner = self.inner.clone();
+        if thread_builder.spawn(move || inner.work()).is_err() {
+            let mut state = self.inner.state.lock().unwrap();
+            state.num_threads -= 1;
+            if state.num_threads == 0 {
+                // Nobody is left to run the queued jobs, so drop them and let
+                // their `SpawnBlocking` futures observe it.
+                state.queue.clear();
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn work(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            state.num_idle += 1;
+            let (guard, timeout) = self.condvar.wait_timeout(state, self.keep_alive).unwrap();
+            state = guard;
+            state.num_idle -= 1;
+            if state.num_notified > 0 {
+                state.num_notified -= 1;
+            }
+
+            if timeout.timed_out() && state.queue.is_empty() {
+                // idle for too long; reap this thread
+                state.num_threads -= 1;
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use futures_channel::oneshot::{self, Receiver, Sender};
+use futures_core::future::Future;
+use futures_core::task::{Context, Poll};
+use futures_util::future::{AbortHandle, Abortable, Aborted, CatchUnwind, FutureExt};
+use pin_utils::{unsafe_pinned, unsafe_unpinned};
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::prelude::v1::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// A handle to a task spawned with
+/// [`ThreadPool::spawn_with_join_handle`](crate::ThreadPool::spawn_with_join_handle)
+/// or
+/// [`LocalSpawner::spawn_local_with_join_handle`](crate::LocalSpawner::spawn_local_with_join_handle).
+///
+/// The handle is a future that resolves to the output of the task once it
+/// completes, or to a [`JoinError`](JoinError) if the task panicked or was
+/// cancelled.
+///
+/// Unlike [`RemoteHandle`](futures_util::future::RemoteHandle), dropping a
+/// `JoinHandle` *detaches* the task: it keeps running in the background and
+/// its output is discarded. Use [`abort`](JoinHandle::abort) to cancel the
+/// task explicitly.
+pub struct JoinHandle<T> {
+    rx: Receiver<thread::Result<T>>,
+    abort_handle: AbortHandle,
+    finished: Arc<AtomicBool>,
+    name: Option<String>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Cancels the task.
+    ///
+    /// The task's future is dropped the next time the executor gets to it,
+    /// without being polled again, and this handle resolves to a
+    /// [`JoinError`](JoinError) for which
+    /// [`is_cancelled`](JoinError::is_cancelled) returns `true`. Aborting a
+    /// task which has already completed has no effect.
+    pub fn abort(&self) {
+        self.abort_handle.abort();
+    }
+
+    /// Returns `true` if the task has completed, panicked or been dropped by
+    /// its executor.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    /// Drops this handle *without* cancelling the task.
+    ///
+    /// This is equivalent to dropping the handle, and exists to make the
+    /// intent explicit at the call site.
+    pub fn detach(self) {}
+
+    /// Returns the name the task was spawned with, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+}
+
+impl<T> Unpin for JoinHandle<T> {}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.rx.poll_unpin(cx) {
+            Poll::Ready(Ok(Ok(output))) => Poll::Ready(Ok(output)),
+            Poll::Ready(Ok(Err(payload))) => {
+                Poll::Ready(Err(JoinError::new(Repr::Panicked(payload), self.name.take())))
+            }
+            Poll::Ready(Err(_)) => {
+                Poll::Ready(Err(JoinError::new(Repr::Cancelled, self.name.take())))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("name", &self.name)
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+/// The error returned by a [`JoinHandle`](JoinHandle) whose task did not run
+/// to completion.
+pub struct JoinError {
+    repr: Repr,
+    name: Option<String>,
+}
+
+enum Repr {
+    Cancelled,
+    Panicked(Box<dyn Any + Send + 'static>),
+}
+
+impl JoinError {
+    fn new(repr: Repr, name: Option<String>) -> JoinError {
+        JoinError { repr, name }
+    }
+
+    /// Returns the name the task was spawned with, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+
+    /// Returns `true` if the task was aborted, or dropped by its executor
+    /// before completing.
+    pub fn is_cancelled(&self) -> bool {
+        match self.repr {
+            Repr::Cancelled => true,
+            Repr::Panicked(_) => false,
+        }
+    }
+
+    /// Returns `true` if the task panicked.
+    pub fn is_panic(&self) -> bool {
+        match self.repr {
+            Repr::Cancelled => false,
+            Repr::Panicked(_) => true,
+        }
+    }
+
+    /// Consumes the error, returning the panic payload if the task panicked.
+    ///
+    /// The payload can be passed to
+    /// [`std::panic::resume_unwind`](std::panic::resume_unwind) to propagate
+    /// the panic to the current thread.
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send + 'static>, JoinError> {
+        match self.repr {
+            Repr::Panicked(payload) => Ok(payload),
+            repr => Err(JoinError { repr, name: self.name }),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.repr {
+            Repr::Cancelled => "cancelled",
+            Repr::Panicked(_) => "panicked",
+        };
+        f.debug_struct("JoinError")
+            .field("kind", &kind)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "task '{}'", name)?,
+            None => write!(f, "task")?,
+        }
+        match self.repr {
+            Repr::Cancelled => write!(f, " was cancelled"),
+            Repr::Panicked(_) => write!(f, " panicked"),
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+type SendMsg<Fut> = thread::Result<<Fut as Future>::Output>;
+
+/// The future actually spawned onto the executor, which sends the output of
+/// `Fut` to the corresponding `JoinHandle`.
+pub(crate) struct JoinTask<Fut: Future> {
+    future: Abortable<CatchUnwind<AssertUnwindSafe<Fut>>>,
+    tx: Option<Sender<SendMsg<Fut>>>,
+    finished: Arc<AtomicBool>,
+}
+
+impl<Fut: Future> JoinTask<Fut> {
+    unsafe_pinned!(future: Abortable<CatchUnwind<AssertUnwindSafe<Fut>>>);
+    unsafe_unpinned!(tx: Option<Sender<SendMsg<Fut>>>);
+}
+
+impl<Fut: Future> Future for JoinTask<Fut> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let output = match self.as_mut().future().poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        self.finished.store(true, Ordering::SeqCst);
+        let tx = self.as_mut().tx().take().unwrap();
+        match output {
+            // if the handle has been detached then that's ok, we just ignore
+            // the send error here.
+            Ok(output) => drop(tx.send(output)),
+            // dropping the sender lets the handle observe the cancellation
+            Err(Aborted) => drop(tx),
+        }
+        Poll::Ready(())
+    }
+}
+
+impl<Fut: Future> Drop for JoinTask<Fut> {
+    fn drop(&mut self) {
+        self.finished.store(true, Ordering::SeqCst);
+    }
+}
+
+pub(crate) fn join_task<Fut: Future>(
+    future: Fut,
+    name: Option<&str>,
+) -> (JoinTask<Fut>, JoinHandle<Fut::Output>) {
+    let (tx, rx) = oneshot::channel();
+    let (abort_handle, abort_registration) = AbortHandle::new_pair();
+    let finished = Arc::new(AtomicBool::new(false));
+
+    // AssertUnwindSafe is used here because `Send + 'static` is basically
+    // an alias for an implementation of the `UnwindSafe` trait but we can't
+    // express that in the standard library right now.
+    let task = JoinTask {
+        future: Abortable::new(AssertUnwindSafe(future).catch_unwind(), abort_registration),
+        tx: Some(tx),
+        finished: finished.clone(),
+    };
+
+    let name = name.map(String::from);
+    (task, JoinHandle { rx, abort_handle, finished, name })
+}
@@ -1,16 +1,24 @@
 use crate::enter;
+use crate::join_handle::{join_task, JoinHandle};
+use crate::metrics::{Counters, Instrumented, Metrics};
+use crate::task_meta::TaskMeta;
 use futures_core::future::{Future, FutureObj, LocalFutureObj};
 use futures_core::stream::{Stream};
-use futures_core::task::{Context, Poll, Spawn, LocalSpawn, SpawnError};
-use futures_util::task::{waker_ref, ArcWake};
+use futures_core::task::{Context, Poll, Spawn, LocalSpawn, SpawnError, TaskOptions, Waker};
+use futures_util::future::FutureExt;
+use futures_util::task::{waker_ref, ArcWake, TaskId};
 use futures_util::stream::FuturesUnordered;
 use futures_util::stream::StreamExt;
 use pin_utils::pin_mut;
-use std::cell::{RefCell};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
 use std::prelude::v1::*;
 use std::rc::{Rc, Weak};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::{self, Thread};
 
 /// A single-threaded task pool for polling futures to completion.
@@ -24,10 +32,12 @@ use std::thread::{self, Thread};
 /// [`spawner()`](LocalPool::spawner) method. Because the executor is
 /// single-threaded, it supports a special form of task spawning for non-`Send`
 /// futures, via [`spawn_local_obj`](LocalSpawner::spawn_local_obj).
-#[derive(Debug)]
 pub struct LocalPool {
-    pool: FuturesUnordered<LocalFutureObj<'static, ()>>,
+    pool: FuturesUnordered<PoolTask>,
     incoming: Rc<Incoming>,
+    counters: Arc<Counters>,
+    budget: Rc<PollBudget>,
+    names: Rc<TaskNames>,
 }
 
 /// A handle to a [`LocalPool`](LocalPool) that implements
@@ -37,21 +47,88 @@ pub struct LocalSpawner {
     incoming: Weak<Incoming>,
 }
 
-type Incoming = RefCell<Vec<LocalFutureObj<'static, ()>>>;
+type Incoming = RefCell<Vec<LocalTask>>;
+
+// The names of the tasks in the pool, for those which have one
+type TaskNames = RefCell<HashMap<TaskId, String>>;
+
+#[derive(Debug)]
+struct LocalTask {
+    future: LocalFutureObj<'static, ()>,
+    meta: TaskMeta,
+}
+
+impl Future for LocalTask {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let LocalTask { ref mut future, ref meta } = *self;
+        meta.enter(|| future.poll_unpin(cx))
+    }
+}
+
+// Limits the number of tasks polled by `LocalPool::try_run_one`, shared by
+// the pool and the tasks in it.
+#[derive(Default)]
+struct PollBudget {
+    // The number of tasks which may still be polled, or `None` if there is
+    // no limit
+    remaining: Cell<Option<usize>>,
+    // Wakers of the tasks which were ready to be polled but held back
+    deferred: RefCell<Vec<Waker>>,
+}
+
+// A task which has been moved from the incoming queue into the pool.
+struct PoolTask {
+    task: Instrumented<LocalTask>,
+    budget: Rc<PollBudget>,
+    names: Rc<TaskNames>,
+}
+
+impl Drop for PoolTask {
+    fn drop(&mut self) {
+        let id = self.task.get_ref().meta.id();
+        self.names.borrow_mut().remove(&id);
+    }
+}
+
+impl Future for PoolTask {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.budget.remaining.get() {
+            Some(0) => {
+                self.budget.deferred.borrow_mut().push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            Some(n) => self.budget.remaining.set(Some(n - 1)),
+            None => {}
+        }
+        self.task.poll_unpin(cx)
+    }
+}
 
 pub(crate) struct ThreadNotify {
-    thread: Thread
+    thread: Thread,
+    // Set when the thread has been woken since the last time it parked, so
+    // that stepping methods can tell whether more progress is possible.
+    unparked: AtomicBool,
 }
 
 thread_local! {
     static CURRENT_THREAD_NOTIFY: Arc<ThreadNotify> = Arc::new(ThreadNotify {
         thread: thread::current(),
+        unparked: AtomicBool::new(false),
     });
 }
 
 impl ArcWake for ThreadNotify {
     fn wake_by_ref(arc_self: &Arc<Self>) {
-        arc_self.thread.unpark();
+        // Avoid a redundant `unpark` if the thread was already woken.
+        let unparked = arc_self.unparked.swap(true, Ordering::Release);
+        if !unparked {
+            arc_self.thread.unpark();
+        }
     }
 }
 
@@ -69,17 +146,36 @@ fn run_executor<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(mut f: F) -> T {
             if let Poll::Ready(t) = f(&mut cx) {
                 return t;
             }
-            thread::park();
+            // Consume the wakeup that occurred while executing `f`, if any.
+            let unparked = thread_notify.unparked.swap(false, Ordering::Acquire);
+            if !unparked {
+                // No wakeup occurred. It may occur now, right before parking,
+                // but in that case the token made available by `unpark()` is
+                // guaranteed to still be available and `park()` is a no-op.
+                thread::park();
+                // Unset `unparked` before the next call to `f` to avoid a
+                // redundant turn of the loop.
+                thread_notify.unparked.store(false, Ordering::Release);
+            }
         }
     })
 }
 
+// Whether the current thread has been woken while running `f` in
+// `run_executor`, meaning that some task may have become ready again.
+fn woken() -> bool {
+    CURRENT_THREAD_NOTIFY.with(|thread_notify| thread_notify.unparked.load(Ordering::Acquire))
+}
+
 impl LocalPool {
     /// Create a new, empty pool of tasks.
     pub fn new() -> LocalPool {
         LocalPool {
             pool: FuturesUnordered::new(),
             incoming: Default::default(),
+            counters: Default::default(),
+            budget: Default::default(),
+            names: Default::default(),
         }
     }
 
@@ -154,18 +250,146 @@ impl LocalPool {
         })
     }
 
+    /// Polls at most one task of the pool which is ready to make progress.
+    ///
+    /// Returns `true` if a task was polled, whether or not it completed, and
+    /// `false` if the pool is empty or every remaining task is waiting to be
+    /// woken.
+    ///
+    /// Unlike [`run`](LocalPool::run) and [`run_until`](LocalPool::run_until),
+    /// this function never blocks the calling thread, which makes it suitable
+    /// for driving the pool from an external event loop:
+    ///
+    /// ```
+    /// use futures::executor::LocalPool;
+    /// use futures::future::ready;
+    /// use futures::task::LocalSpawnExt;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// let mut spawner = pool.spawner();
+    ///
+    /// spawner.spawn_local(ready(())).unwrap();
+    /// spawner.spawn_local(ready(())).unwrap();
+    ///
+    /// // run one task at a time
+    /// assert!(pool.try_run_one());
+    /// assert_eq!(pool.num_tasks(), 1);
+    /// assert!(pool.try_run_one());
+    /// assert!(!pool.try_run_one());
+    /// ```
+    pub fn try_run_one(&mut self) -> bool {
+        // Lifts the limit and lets the tasks which were held back run next
+        // time, even if polling panics.
+        struct Reset<'a>(&'a PollBudget);
+
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.remaining.set(None);
+                for waker in self.0.deferred.borrow_mut().drain(..) {
+                    waker.wake();
+                }
+            }
+        }
+
+        run_executor(|cx| {
+            self.drain_incoming();
+            let budget = self.budget.clone();
+            let _reset = Reset(&budget);
+            budget.remaining.set(Some(1));
+            let _ = self.pool.poll_next_unpin(cx);
+            Poll::Ready(budget.remaining.get() == Some(0))
+        })
+    }
+
+    /// Runs all tasks in the pool and returns once no more progress can be
+    /// made, i.e. once every remaining task is waiting to be woken.
+    ///
+    /// Unlike [`run`](LocalPool::run), this function never blocks the calling
+    /// thread waiting for a wakeup, which makes it suitable for deterministic
+    /// tests and for integrating with an external event loop:
+    ///
+    /// ```
+    /// use futures::channel::oneshot;
+    /// use futures::executor::LocalPool;
+    /// use futures::future::FutureExt;
+    /// use futures::task::LocalSpawnExt;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// let mut spawner = pool.spawner();
+    /// let (tx, rx) = oneshot::channel::<()>();
+    ///
+    /// spawner.spawn_local(rx.map(|_| ())).unwrap();
+    /// pool.run_until_stalled();
+    /// assert_eq!(pool.num_tasks(), 1);
+    ///
+    /// tx.send(()).unwrap();
+    /// pool.run_until_stalled();
+    /// assert_eq!(pool.num_tasks(), 0);
+    /// ```
+    pub fn run_until_stalled(&mut self) {
+        run_executor(|cx| match self.poll_pool(cx) {
+            // the pool is empty
+            Poll::Ready(()) => Poll::Ready(()),
+            Poll::Pending => {
+                if woken() {
+                    Poll::Pending
+                } else {
+                    // we're stalled for now
+                    Poll::Ready(())
+                }
+            }
+        })
+    }
+
+    /// Returns the number of tasks currently spawned onto the pool which have
+    /// not completed yet, including ones that have not been polled yet.
+    pub fn num_tasks(&self) -> usize {
+        self.pool.len() + self.incoming.borrow().len()
+    }
+
+    // The names of the tasks which have not completed yet, for those which
+    // have one.
+    fn task_names(&self) -> Vec<String> {
+        let incoming = self.incoming.borrow();
+        self.names.borrow().values()
+            .cloned()
+            .chain(incoming.iter().filter_map(|task| task.meta.name().map(String::from)))
+            .collect()
+    }
+
+    /// Returns a snapshot of the pool's counters.
+    ///
+    /// See [`Metrics`](crate::Metrics) for details. Tasks are counted as
+    /// spawned as soon as they are handed to a
+    /// [`LocalSpawner`](LocalSpawner), even if the pool has not run since.
+    pub fn metrics(&mut self) -> Metrics {
+        self.drain_incoming();
+        self.counters.snapshot(self.pool.len(), self.task_names(), Vec::new(), 0)
+    }
+
+    // Move newly-spawned tasks from the incoming queue into the pool.
+    fn drain_incoming(&mut self) {
+        let mut incoming = self.incoming.borrow_mut();
+        for task in incoming.drain(..) {
+            self.counters.record_spawn();
+            if let Some(name) = task.meta.name() {
+                self.names.borrow_mut().insert(task.meta.id(), name.to_string());
+            }
+            self.pool.push(PoolTask {
+                task: Instrumented::new(task, self.counters.clone()),
+                budget: self.budget.clone(),
+                names: self.names.clone(),
+            })
+        }
+    }
+
     // Make maximal progress on the entire pool of spawned task, returning `Ready`
     // if the pool is empty and `Pending` if no further progress can be made.
     fn poll_pool(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         // state for the FuturesUnordered, which will never be used
         loop {
             // empty the incoming queue of newly-spawned tasks
-            {
-                let mut incoming = self.incoming.borrow_mut();
-                for task in incoming.drain(..) {
-                    self.pool.push(task)
-                }
-            }
+            self.drain_incoming();
 
             let ret = self.pool.poll_next_unpin(cx);
             // we queued up some new tasks; add them and poll again
@@ -183,6 +407,15 @@ impl LocalPool {
     }
 }
 
+impl fmt::Debug for LocalPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalPool")
+            .field("num_tasks", &self.num_tasks())
+            .field("task_names", &self.task_names())
+            .finish()
+    }
+}
+
 impl Default for LocalPool {
     fn default() -> Self {
         Self::new()
@@ -239,17 +472,69 @@ impl<S: Stream + Unpin> Iterator for BlockingStream<S> {
     }
 }
 
+impl LocalSpawner {
+    /// Spawns a task that polls the given future to completion, returning a
+    /// [`JoinHandle`](crate::JoinHandle) that resolves to its output.
+    ///
+    /// The future does not need to be `Send`. Dropping the returned handle
+    /// detaches the task rather than cancelling it. If the future panics, the
+    /// panic is caught and reported through the handle instead of unwinding
+    /// out of the pool.
+    ///
+    /// ```
+    /// use futures::executor::LocalPool;
+    /// use futures::future;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// let mut spawner = pool.spawner();
+    /// let handle = spawner.spawn_local_with_join_handle(future::ready(1)).unwrap();
+    /// assert_eq!(pool.run_until(handle).unwrap(), 1);
+    /// ```
+    pub fn spawn_local_with_join_handle<Fut>(
+        &mut self,
+        future: Fut,
+    ) -> Result<JoinHandle<Fut::Output>, SpawnError>
+    where
+        Fut: Future + 'static,
+    {
+        self.spawn_local_with_join_handle_with(future, &TaskOptions::new())
+    }
+
+    /// Spawns a task with the given options, returning a
+    /// [`JoinHandle`](crate::JoinHandle) that resolves to its output.
+    ///
+    /// This is the same as
+    /// [`spawn_local_with_join_handle`](LocalSpawner::spawn_local_with_join_handle),
+    /// except that the task's name is also reported by the handle and its
+    /// [`JoinError`](crate::JoinError).
+    pub fn spawn_local_with_join_handle_with<Fut>(
+        &mut self,
+        future: Fut,
+        options: &TaskOptions<'_>,
+    ) -> Result<JoinHandle<Fut::Output>, SpawnError>
+    where
+        Fut: Future + 'static,
+    {
+        let (task, handle) = join_task(future, options.name());
+        self.spawn_local_obj_with(LocalFutureObj::new(Box::new(task)), options)?;
+        Ok(handle)
+    }
+}
+
 impl Spawn for LocalSpawner {
     fn spawn_obj(
         &mut self,
         future: FutureObj<'static, ()>,
     ) -> Result<(), SpawnError> {
-        if let Some(incoming) = self.incoming.upgrade() {
-            incoming.borrow_mut().push(future.into());
-            Ok(())
-        } else {
-            Err(SpawnError::shutdown())
-        }
+        self.spawn_local_obj_with(future.into(), &TaskOptions::new())
+    }
+
+    fn spawn_obj_with(
+        &mut self,
+        future: FutureObj<'static, ()>,
+        options: &TaskOptions<'_>,
+    ) -> Result<(), SpawnError> {
+        self.spawn_local_obj_with(future.into(), options)
     }
 
     fn status(&self) -> Result<(), SpawnError> {
@@ -265,9 +550,20 @@ impl LocalSpawn for LocalSpawner {
     fn spawn_local_obj(
         &mut self,
         future: LocalFutureObj<'static, ()>,
+    ) -> Result<(), SpawnError> {
+        self.spawn_local_obj_with(future, &TaskOptions::new())
+    }
+
+    fn spawn_local_obj_with(
+        &mut self,
+        future: LocalFutureObj<'static, ()>,
+        options: &TaskOptions<'_>,
     ) -> Result<(), SpawnError> {
         if let Some(incoming) = self.incoming.upgrade() {
-            incoming.borrow_mut().push(future);
+            incoming.borrow_mut().push(LocalTask {
+                future,
+                meta: TaskMeta::new(options),
+            });
             Ok(())
         } else {
             Err(SpawnError::shutdown())
@@ -0,0 +1,173 @@
+use futures_core::future::Future;
+use futures_core::task::{Context, Poll};
+use pin_utils::unsafe_pinned;
+use std::pin::Pin;
+use std::prelude::v1::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const NUM_BUCKETS: usize = 6;
+
+// Upper bounds of all but the last bucket of the poll duration histogram
+const BUCKET_BOUNDS: [Duration; NUM_BUCKETS - 1] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+];
+
+/// A snapshot of the counters of an executor.
+///
+/// Returned by [`ThreadPool::metrics`](crate::ThreadPool::metrics) and
+/// [`LocalPool::metrics`](crate::LocalPool::metrics).
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    tasks_spawned: usize,
+    tasks_alive: usize,
+    task_names: Vec<String>,
+    total_polls: usize,
+    poll_durations: [usize; NUM_BUCKETS],
+    queue_depths: Vec<usize>,
+    injector_depth: usize,
+}
+
+impl Metrics {
+    /// The total number of tasks spawned onto the executor.
+    pub fn tasks_spawned(&self) -> usize {
+        self.tasks_spawned
+    }
+
+    /// The number of spawned tasks which have not completed yet.
+    pub fn tasks_alive(&self) -> usize {
+        self.tasks_alive
+    }
+
+    /// The names of the spawned tasks which have not completed yet, sorted.
+    ///
+    /// Only the tasks spawned with a name, for example through
+    /// [`SpawnExt::build_task`](futures_util::task::SpawnExt::build_task),
+    /// are listed.
+    pub fn task_names(&self) -> &[String] {
+        &self.task_names
+    }
+
+    /// The total number of times a task has been polled.
+    pub fn total_polls(&self) -> usize {
+        self.total_polls
+    }
+
+    /// A histogram of the time taken by each poll of a task.
+    ///
+    /// Element `i` counts the polls which took less than
+    /// [`poll_duration_bounds()[i]`](Metrics::poll_duration_bounds), but not
+    /// less than the previous bound. The last element counts the polls which
+    /// took longer than every bound; a growing count there usually points at
+    /// a task blocking its executor.
+    pub fn poll_duration_histogram(&self) -> &[usize] {
+        &self.poll_durations
+    }
+
+    /// The upper bounds of the buckets of
+    /// [`poll_duration_histogram`](Metrics::poll_duration_histogram), from
+    /// 10µs to 100ms in powers of ten.
+    pub fn poll_duration_bounds() -> &'static [Duration] {
+        &BUCKET_BOUNDS
+    }
+
+    /// The number of tasks waiting to be polled in the local run queue of
+    /// each worker thread.
+    ///
+    /// This is always empty for a [`LocalPool`](crate::LocalPool).
+    pub fn queue_depths(&self) -> &[usize] {
+        &self.queue_depths
+    }
+
+    /// The number of tasks waiting to be picked up by a worker thread from
+    /// the executor's shared queue.
+    ///
+    /// This is always zero for a [`LocalPool`](crate::LocalPool).
+    pub fn injector_depth(&self) -> usize {
+        self.injector_depth
+    }
+}
+
+/// Counters shared by an executor and its tasks.
+#[derive(Default)]
+pub(crate) struct Counters {
+    spawned: AtomicUsize,
+    polls: AtomicUsize,
+    poll_durations: [AtomicUsize; NUM_BUCKETS],
+}
+
+impl Counters {
+    pub(crate) fn record_spawn(&self) {
+        self.spawned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_poll(&self, duration: Duration) {
+        let bucket = BUCKET_BOUNDS.iter()
+            .position(|bound| duration < *bound)
+            .unwrap_or(NUM_BUCKETS - 1);
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_durations[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(
+        &self,
+        tasks_alive: usize,
+        mut task_names: Vec<String>,
+        queue_depths: Vec<usize>,
+        injector_depth: usize,
+    ) -> Metrics {
+        let mut poll_durations = [0; NUM_BUCKETS];
+        for (count, counter) in poll_durations.iter_mut().zip(&self.poll_durations) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        task_names.sort();
+        Metrics {
+            tasks_spawned: self.spawned.load(Ordering::Relaxed),
+            tasks_alive,
+            task_names,
+            total_polls: self.polls.load(Ordering::Relaxed),
+            poll_durations,
+            queue_depths,
+            injector_depth,
+        }
+    }
+}
+
+/// A future which records how long each of its polls takes.
+///
+/// Used by executors, like `LocalPool`, which do not poll each task
+/// themselves.
+pub(crate) struct Instrumented<Fut> {
+    future: Fut,
+    counters: Arc<Counters>,
+}
+
+impl<Fut: Unpin> Unpin for Instrumented<Fut> {}
+
+impl<Fut> Instrumented<Fut> {
+    unsafe_pinned!(future: Fut);
+
+    pub(crate) fn new(future: Fut, counters: Arc<Counters>) -> Instrumented<Fut> {
+        Instrumented { future, counters }
+    }
+
+    pub(crate) fn get_ref(&self) -> &Fut {
+        &self.future
+    }
+}
+
+impl<Fut: Future> Future for Instrumented<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Fut::Output> {
+        let start = Instant::now();
+        let res = self.as_mut().future().poll(cx);
+        self.counters.record_poll(start.elapsed());
+        res
+    }
+}
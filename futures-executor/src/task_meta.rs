@@ -0,0 +1,36 @@
+use futures_core::task::TaskOptions;
+use futures_util::task::TaskId;
+use std::prelude::v1::*;
+
+/// The identity of a spawned task: a fresh id, and the name it was spawned
+/// with, if any.
+#[derive(Debug)]
+pub(crate) struct TaskMeta {
+    id: TaskId,
+    name: Option<String>,
+}
+
+impl TaskMeta {
+    pub(crate) fn new(options: &TaskOptions<'_>) -> TaskMeta {
+        TaskMeta {
+            id: TaskId::next(),
+            name: options.name().map(String::from),
+        }
+    }
+
+    pub(crate) fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+
+    /// Runs `f`, which polls the task, with the task's id set as the current
+    /// one.
+    pub(crate) fn enter<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        self.id.enter(f)
+    }
+}
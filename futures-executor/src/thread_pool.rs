@@ -1,16 +1,25 @@
+use crate::blocking::{BlockingPool, SpawnBlocking};
 use crate::enter;
+use crate::join_handle::{join_task, JoinHandle};
+use crate::metrics::{Counters, Metrics};
+use crate::task_meta::TaskMeta;
 use crate::unpark_mutex::UnparkMutex;
 use futures_core::future::{Future, FutureObj};
-use futures_core::task::{Context, Poll, Spawn, SpawnError};
+use futures_core::task::{Context, Poll, Spawn, SpawnError, TaskOptions};
 use futures_util::future::FutureExt;
-use futures_util::task::{ArcWake, waker_ref};
+use futures_util::task::{ArcWake, TaskId, waker_ref};
 use num_cpus;
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
 use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
 use std::prelude::v1::*;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
 use std::thread;
+use std::time::{Duration, Instant};
 use std::fmt;
 
 /// A general-purpose thread pool for scheduling tasks that poll futures to
@@ -19,8 +28,17 @@ use std::fmt;
 /// The thread pool multiplexes any number of tasks onto a fixed number of
 /// worker threads.
 ///
+/// Each worker thread owns a local run queue. Tasks spawned or woken from a
+/// worker thread are pushed onto that worker's queue, while tasks spawned from
+/// outside the pool go through a shared injector queue. Idle workers steal
+/// work from their siblings, so no single lock is shared by every worker.
+///
 /// This type is a clonable handle to the threadpool itself.
 /// Cloning it will only create a new reference, not a new threadpool.
+///
+/// Dropping the last handle lets the worker threads exit in the background
+/// once every task has completed. Use [`shutdown`](ThreadPool::shutdown) to
+/// stop accepting new tasks and wait for the worker threads to exit.
 pub struct ThreadPool {
     state: Arc<PoolState>,
 }
@@ -30,20 +48,112 @@ pub struct ThreadPoolBuilder {
     pool_size: usize,
     stack_size: usize,
     name_prefix: Option<String>,
-    after_start: Option<Arc<dyn Fn(usize) + Send + Sync>>,
-    before_stop: Option<Arc<dyn Fn(usize) + Send + Sync>>,
+    after_start: Option<WorkerHook>,
+    before_stop: Option<WorkerHook>,
+    hooks: TaskHooks,
+    shutdown_policy: ShutdownPolicy,
+    panic_policy: PanicPolicy,
+    panic_handler: Option<PanicHandler>,
+    max_blocking_threads: usize,
+    blocking_keep_alive: Duration,
+}
+
+// Per-task hooks, shared by every worker thread of a pool
+#[derive(Clone, Default)]
+struct TaskHooks {
+    on_task_spawn: Option<Arc<dyn Fn() + Send + Sync>>,
+    before_poll: Option<WorkerHook>,
+    after_poll: Option<Arc<dyn Fn(usize, Duration) + Send + Sync>>,
+    on_task_complete: Option<WorkerHook>,
+}
+
+/// What a [`ThreadPool`](ThreadPool) does with its remaining tasks when it is
+/// shut down.
+///
+/// See [`ThreadPoolBuilder::shutdown_policy`](ThreadPoolBuilder::shutdown_policy).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Keep polling the tasks already spawned onto the pool until every one
+    /// of them has completed, then stop the worker threads.
+    Drain,
+    /// Drop every task that is not currently being polled, without polling it
+    /// again, then stop the worker threads. This includes the tasks waiting
+    /// to be woken up.
+    Cancel,
+}
+
+/// What a [`ThreadPool`](ThreadPool) does when one of its tasks panics.
+///
+/// See [`ThreadPoolBuilder::panic_policy`](ThreadPoolBuilder::panic_policy).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Catch the panic and drop the task, passing the panic payload to the
+    /// [`panic_handler`](ThreadPoolBuilder::panic_handler) if one is set.
+    /// The worker thread carries on with other tasks.
+    Catch,
+    /// Abort the process.
+    Abort,
+    /// Let the panic unwind the worker thread, which is then replaced by a
+    /// new one.
+    Propagate,
 }
 
+type WorkerHook = Arc<dyn Fn(usize) + Send + Sync>;
+type PanicHandler = Arc<dyn Fn(Option<&str>, Box<dyn Any + Send>) + Send + Sync>;
+
 trait AssertSendSync: Send + Sync {}
 impl AssertSendSync for ThreadPool {}
 
 struct PoolState {
-    tx: Mutex<mpsc::Sender<Message>>,
-    rx: Mutex<mpsc::Receiver<Message>>,
+    // Tasks spawned or woken from outside of the pool's worker threads
+    injector: Mutex<VecDeque<Task>>,
+    // Local run queues, one per worker thread
+    workers: Vec<WorkerQueue>,
+    // Idle workers park on `sleep_cv`; `num_sleeping` lets the spawning side
+    // skip taking the `sleep` lock when every worker is busy.
+    sleep: Mutex<()>,
+    sleep_cv: Condvar,
+    num_sleeping: AtomicUsize,
+    // Set once every `ThreadPool` handle has been dropped
+    closed: AtomicBool,
+    // Set by `ThreadPool::shutdown`; no new tasks are accepted afterwards
+    shutdown: AtomicBool,
+    shutdown_policy: ShutdownPolicy,
+    // Number of spawned tasks which have neither completed nor been cancelled
+    num_tasks: AtomicUsize,
+    // Every spawned task which has neither completed nor been cancelled, so
+    // that a cancelling shutdown can reach the ones waiting to be woken up
+    tasks: Mutex<HashMap<TaskId, Weak<WakeHandle>>>,
+    // Number of worker threads which have not exited yet
+    running: Mutex<usize>,
+    running_cv: Condvar,
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+    panic_policy: PanicPolicy,
+    panic_handler: Option<PanicHandler>,
+    // Used to spawn replacements for worker threads that panicked
+    stack_size: usize,
+    name_prefix: Option<String>,
+    blocking: BlockingPool,
+    hooks: TaskHooks,
+    counters: Counters,
     cnt: AtomicUsize,
     size: usize,
 }
 
+/// The run queue owned by a single worker thread.
+///
+/// The owning worker pops from the front, while idle siblings steal half of
+/// the queue from the back.
+struct WorkerQueue {
+    tasks: Mutex<VecDeque<Task>>,
+}
+
+thread_local! {
+    // The pool (by address) and index of the worker running on this thread,
+    // if any.
+    static CURRENT_WORKER: Cell<Option<(usize, usize)>> = Cell::new(None);
+}
+
 impl fmt::Debug for ThreadPool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ThreadPool")
@@ -57,15 +167,14 @@ impl fmt::Debug for ThreadPoolBuilder {
         f.debug_struct("ThreadPoolBuilder")
             .field("pool_size", &self.pool_size)
             .field("name_prefix", &self.name_prefix)
+            .field("shutdown_policy", &self.shutdown_policy)
+            .field("panic_policy", &self.panic_policy)
+            .field("max_blocking_threads", &self.max_blocking_threads)
+            .field("blocking_keep_alive", &self.blocking_keep_alive)
             .finish()
     }
 }
 
-enum Message {
-    Run(Task),
-    Close,
-}
-
 impl ThreadPool {
     /// Creates a new thread pool with the default configuration.
     ///
@@ -97,6 +206,198 @@ impl ThreadPool {
     pub fn run<F: Future>(&mut self, f: F) -> F::Output {
         crate::LocalPool::new().run_until(f)
     }
+
+    /// Shuts the thread pool down, blocking until all worker threads have
+    /// exited.
+    ///
+    /// Once this method has been called, spawning onto the pool (through any
+    /// of its handles) fails with
+    /// [`SpawnError::shutdown`](futures_core::task::SpawnError::shutdown).
+    /// Tasks spawned before the call are either run to completion or dropped,
+    /// according to the pool's
+    /// [`shutdown_policy`](ThreadPoolBuilder::shutdown_policy). With the
+    /// default [`ShutdownPolicy::Drain`](ShutdownPolicy::Drain), this method
+    /// will block forever if a task never completes; consider
+    /// [`shutdown_timeout`](ThreadPool::shutdown_timeout) instead.
+    ///
+    /// Closures passed to [`spawn_blocking`](ThreadPool::spawn_blocking) are
+    /// not affected by shutdown.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from one of the pool's own worker threads.
+    pub fn shutdown(&self) {
+        self.shutdown_inner(None);
+    }
+
+    /// Shuts the thread pool down, blocking until all worker threads have
+    /// exited or until `timeout` has elapsed.
+    ///
+    /// Returns `true` if all worker threads exited in time. Otherwise the
+    /// pool stays shut down to new tasks, the remaining worker threads keep
+    /// applying the shutdown policy in the background, and this method or
+    /// [`shutdown`](ThreadPool::shutdown) may be called again to wait for them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from one of the pool's own worker threads.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> bool {
+        self.shutdown_inner(Some(timeout))
+    }
+
+    fn shutdown_inner(&self, timeout: Option<Duration>) -> bool {
+        let current = CURRENT_WORKER.with(|current| current.get());
+        if let Some((id, _)) = current {
+            assert!(id != self.state.id(),
+                    "cannot shut down a `ThreadPool` from one of its own \
+                     worker threads");
+        }
+
+        self.state.begin_shutdown();
+        if !self.state.wait_for_workers(timeout) {
+            return false;
+        }
+        // Workers replacing panicked ones push their handle from the thread
+        // they replace, so keep going until no handle is left.
+        loop {
+            let threads = self.state.threads.lock().unwrap().drain(..).collect::<Vec<_>>();
+            if threads.is_empty() {
+                return true;
+            }
+            for thread in threads {
+                // A worker thread may only fail by panicking in a hook or
+                // task, which has already been reported on that thread.
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Runs the blocking closure `f` on a dedicated thread, returning a future
+    /// which resolves to its return value.
+    ///
+    /// Blocking inside of a task (for example on file system access or a
+    /// synchronous library call) prevents the worker thread from polling any
+    /// other task. Such work should instead be moved onto the pool's separate
+    /// set of blocking threads through this method. Blocking threads are
+    /// spawned on demand, up to
+    /// [`max_blocking_threads`](ThreadPoolBuilder::max_blocking_threads), and
+    /// exit after being idle for
+    /// [`blocking_keep_alive`](ThreadPoolBuilder::blocking_keep_alive). When
+    /// all of them are busy, closures are queued until one frees up.
+    ///
+    /// If `f` panics, the panic is propagated to the task polling the
+    /// returned future.
+    ///
+    /// Blocking threads are not affected by
+    /// [`shutdown`](ThreadPool::shutdown): this method keeps accepting
+    /// closures after the pool has been shut down, and shutting down does not
+    /// wait for the closures already running.
+    ///
+    /// ```
+    /// use futures::executor::{block_on, ThreadPool};
+    ///
+    /// let pool = ThreadPool::new().unwrap();
+    /// let sum = pool.spawn_blocking(|| (1..=10).sum::<u32>());
+    /// assert_eq!(block_on(sum), 55);
+    /// ```
+    pub fn spawn_blocking<F, T>(&self, f: F) -> SpawnBlocking<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.state.blocking.spawn(f)
+    }
+
+    /// Returns a snapshot of the pool's counters.
+    ///
+    /// See [`Metrics`](crate::Metrics) for details. The counters are updated
+    /// concurrently by the worker threads, so the snapshot is not guaranteed
+    /// to be consistent across fields.
+    ///
+    /// ```
+    /// use futures::executor::{block_on, ThreadPool};
+    /// use futures::future;
+    ///
+    /// let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+    /// let handle = pool.spawn_with_join_handle(future::ready(())).unwrap();
+    /// block_on(handle).unwrap();
+    ///
+    /// let metrics = pool.metrics();
+    /// assert_eq!(metrics.tasks_spawned(), 1);
+    /// assert_eq!(metrics.queue_depths().len(), 2);
+    /// ```
+    pub fn metrics(&self) -> Metrics {
+        let state = &self.state;
+        let task_names = state.tasks.lock().unwrap().values()
+            .filter_map(Weak::upgrade)
+            .filter_map(|wake_handle| wake_handle.meta.name().map(String::from))
+            .collect();
+        state.counters.snapshot(
+            state.num_tasks.load(Ordering::SeqCst),
+            task_names,
+            state.workers.iter().map(WorkerQueue::len).collect(),
+            state.injector.lock().unwrap().len(),
+        )
+    }
+
+    /// Spawns a task that polls the given future to completion, returning a
+    /// [`JoinHandle`](crate::JoinHandle) that resolves to its output.
+    ///
+    /// Dropping the returned handle detaches the task rather than cancelling
+    /// it. If the future panics, the panic is caught and reported through the
+    /// handle instead of unwinding the worker thread.
+    ///
+    /// ```
+    /// use futures::executor::{block_on, ThreadPool};
+    /// use futures::future;
+    ///
+    /// let mut pool = ThreadPool::new().unwrap();
+    /// let handle = pool.spawn_with_join_handle(future::ready(1)).unwrap();
+    /// assert_eq!(block_on(handle).unwrap(), 1);
+    /// ```
+    pub fn spawn_with_join_handle<Fut>(
+        &mut self,
+        future: Fut,
+    ) -> Result<JoinHandle<Fut::Output>, SpawnError>
+    where
+        Fut: Future + Send + 'static,
+        Fut::Output: Send,
+    {
+        self.spawn_with_join_handle_with(future, &TaskOptions::new())
+    }
+
+    /// Spawns a task with the given options, returning a
+    /// [`JoinHandle`](crate::JoinHandle) that resolves to its output.
+    ///
+    /// This is the same as
+    /// [`spawn_with_join_handle`](ThreadPool::spawn_with_join_handle), except
+    /// that the task's name is also reported by the handle and its
+    /// [`JoinError`](crate::JoinError).
+    ///
+    /// ```
+    /// use futures::executor::{block_on, ThreadPool};
+    /// use futures::future;
+    /// use futures::task::TaskOptions;
+    ///
+    /// let mut pool = ThreadPool::new().unwrap();
+    /// let options = TaskOptions::new().with_name("conn-42");
+    /// let handle = pool.spawn_with_join_handle_with(future::ready(1), &options).unwrap();
+    /// assert_eq!(handle.name(), Some("conn-42"));
+    /// assert_eq!(block_on(handle).unwrap(), 1);
+    /// ```
+    pub fn spawn_with_join_handle_with<Fut>(
+        &mut self,
+        future: Fut,
+        options: &TaskOptions<'_>,
+    ) -> Result<JoinHandle<Fut::Output>, SpawnError>
+    where
+        Fut: Future + Send + 'static,
+        Fut::Output: Send,
+    {
+        let (task, handle) = join_task(future, options.name());
+        self.spawn_obj_with(FutureObj::new(Box::new(task)), options)?;
+        Ok(handle)
+    }
 }
 
 impl Spawn for ThreadPool {
@@ -106,6 +407,18 @@ impl Spawn for ThreadPool {
     ) -> Result<(), SpawnError> {
         (&*self).spawn_obj(future)
     }
+
+    fn spawn_obj_with(
+        &mut self,
+        future: FutureObj<'static, ()>,
+        options: &TaskOptions<'_>,
+    ) -> Result<(), SpawnError> {
+        (&*self).spawn_obj_with(future, options)
+    }
+
+    fn status(&self) -> Result<(), SpawnError> {
+        self.state.status()
+    }
 }
 
 impl Spawn for &ThreadPool {
@@ -113,42 +426,412 @@ impl Spawn for &ThreadPool {
         &mut self,
         future: FutureObj<'static, ()>,
     ) -> Result<(), SpawnError> {
+        self.spawn_obj_with(future, &TaskOptions::new())
+    }
+
+    fn spawn_obj_with(
+        &mut self,
+        future: FutureObj<'static, ()>,
+        options: &TaskOptions<'_>,
+    ) -> Result<(), SpawnError> {
+        // Count the task before checking for shutdown, so that a concurrent
+        // `shutdown` either rejects it here or waits for it to complete.
+        self.state.num_tasks.fetch_add(1, Ordering::SeqCst);
+        if self.state.shutdown.load(Ordering::SeqCst) {
+            self.state.task_done();
+            return Err(SpawnError::shutdown());
+        }
+        self.state.counters.record_spawn();
+        let meta = TaskMeta::new(options);
+        if let Some(ref on_task_spawn) = self.state.hooks.on_task_spawn {
+            if !self.state.run_task_hook(&meta, || on_task_spawn()) {
+                return Ok(());
+            }
+        }
+
+        let wake_handle = Arc::new(WakeHandle {
+            exec: self.clone(),
+            mutex: UnparkMutex::new(),
+            meta,
+        });
+        self.state.tasks.lock().unwrap()
+            .insert(wake_handle.meta.id(), Arc::downgrade(&wake_handle));
         let task = Task {
             future,
-            wake_handle: Arc::new(WakeHandle {
-                exec: self.clone(),
-                mutex: UnparkMutex::new(),
-            }),
+            wake_handle,
             exec: self.clone(),
         };
-        self.state.send(Message::Run(task));
+        self.state.schedule(task);
         Ok(())
     }
+
+    fn status(&self) -> Result<(), SpawnError> {
+        self.state.status()
+    }
 }
 
 impl PoolState {
-    fn send(&self, msg: Message) {
-        self.tx.lock().unwrap().send(msg).unwrap();
+    fn id(&self) -> usize {
+        self as *const PoolState as usize
+    }
+
+    /// Push a runnable task, preferring the local queue of the current worker
+    /// thread when called from within this pool.
+    fn schedule(&self, task: Task) {
+        if self.is_cancelled() {
+            return self.cancel_task(task);
+        }
+        let current = CURRENT_WORKER.with(|current| current.get());
+        match current {
+            Some((id, idx)) if id == self.id() => self.workers[idx].push(task),
+            _ => self.injector.lock().unwrap().push_back(task),
+        }
+        self.notify_one();
+    }
+
+    fn notify_one(&self) {
+        // Pairs with the increment of `num_sleeping` in `next_task`: either
+        // the sleeping worker sees the task we just pushed, or we see the
+        // worker and wake it up.
+        atomic::fence(Ordering::SeqCst);
+        if self.num_sleeping.load(Ordering::SeqCst) > 0 {
+            let _guard = self.sleep.lock().unwrap();
+            self.sleep_cv.notify_one();
+        }
+    }
+
+    fn notify_all(&self) {
+        let _guard = self.sleep.lock().unwrap();
+        self.sleep_cv.notify_all();
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.notify_all();
+    }
+
+    fn begin_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if self.shutdown_policy == ShutdownPolicy::Cancel {
+            // Tasks waiting to be woken up are only dropped once scheduled
+            // again. Tasks parking from now on cancel themselves in
+            // `Task::run`. Cancelling a task unregisters it, so the lock is
+            // released first.
+            let parked = self.tasks.lock().unwrap().values()
+                .filter_map(Weak::upgrade)
+                .collect::<Vec<_>>();
+            for wake_handle in &parked {
+                WakeHandle::wake_by_ref(wake_handle);
+            }
+        }
+        self.notify_all();
+    }
+
+    fn status(&self) -> Result<(), SpawnError> {
+        if self.shutdown.load(Ordering::SeqCst) {
+            Err(SpawnError::shutdown())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.shutdown_policy == ShutdownPolicy::Cancel &&
+            self.shutdown.load(Ordering::SeqCst)
+    }
+
+    // Whether idle workers may exit, i.e. no more tasks will be scheduled.
+    fn is_done(&self) -> bool {
+        self.closed.load(Ordering::SeqCst) ||
+            (self.shutdown.load(Ordering::SeqCst) &&
+             (self.shutdown_policy == ShutdownPolicy::Cancel ||
+              self.num_tasks.load(Ordering::SeqCst) == 0))
+    }
+
+    // Called whenever a spawned task completes or is cancelled.
+    fn task_done(&self) {
+        if self.num_tasks.fetch_sub(1, Ordering::SeqCst) == 1 &&
+            self.shutdown.load(Ordering::SeqCst)
+        {
+            // Idle workers may be waiting for the last task to drain.
+            self.notify_all();
+        }
+    }
+
+    // Poll a task's future on worker `idx`, running the poll hooks and
+    // recording the poll's duration.
+    fn poll_task(
+        &self,
+        idx: usize,
+        meta: &TaskMeta,
+        future: &mut FutureObj<'static, ()>,
+        cx: &mut Context<'_>,
+    ) -> thread::Result<Poll<()>> {
+        // `FutureObj` is not `UnwindSafe`, but the future is dropped without
+        // being polled again if the poll panics.
+        panic::catch_unwind(AssertUnwindSafe(|| meta.enter(|| {
+            if let Some(ref before_poll) = self.hooks.before_poll {
+                before_poll(idx);
+            }
+            let start = Instant::now();
+            let res = future.poll_unpin(cx);
+            let elapsed = start.elapsed();
+            self.counters.record_poll(elapsed);
+            if let Some(ref after_poll) = self.hooks.after_poll {
+                after_poll(idx, elapsed);
+            }
+            res
+        })))
+    }
+
+    // Run a hook about the task described by `meta`, handling a panic in the
+    // hook like one of the task. Returns whether the hook returned normally.
+    fn run_task_hook<F: FnOnce()>(&self, meta: &TaskMeta, f: F) -> bool {
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(()) => true,
+            Err(payload) => {
+                self.task_panicked(meta, payload);
+                false
+            }
+        }
+    }
+
+    // Called when polling a task, or running a hook about it, panicked,
+    // after the task has been dropped.
+    fn task_panicked(&self, meta: &TaskMeta, payload: Box<dyn Any + Send>) {
+        self.unregister(meta);
+        self.task_done();
+        match self.panic_policy {
+            PanicPolicy::Catch => {
+                if let Some(ref panic_handler) = self.panic_handler {
+                    panic_handler(meta.name(), payload);
+                }
+            }
+            PanicPolicy::Abort => process::abort(),
+            PanicPolicy::Propagate => panic::resume_unwind(payload),
+        }
+    }
+
+    fn cancel_task(&self, task: Task) {
+        self.unregister(&task.wake_handle.meta);
+        drop(task);
+        self.task_done();
+    }
+
+    // Remove a task which has completed or been cancelled from `tasks`. Its
+    // wake handle may outlive it, in wakers held by other tasks.
+    fn unregister(&self, meta: &TaskMeta) {
+        self.tasks.lock().unwrap().remove(&meta.id());
+    }
+
+    // Block until all worker threads have exited, or until `timeout` elapses.
+    fn wait_for_workers(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut running = self.running.lock().unwrap();
+        while *running > 0 {
+            running = match deadline {
+                None => self.running_cv.wait(running).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    self.running_cv.wait_timeout(running, deadline - now).unwrap().0
+                }
+            };
+        }
+        true
+    }
+
+    // Look for a runnable task: first in the worker's own queue, then in the
+    // injector, and finally by stealing from sibling workers.
+    fn find_task(&self, idx: usize) -> Option<Task> {
+        if let Some(task) = self.workers[idx].pop() {
+            return Some(task);
+        }
+        if let Some(task) = self.injector.lock().unwrap().pop_front() {
+            return Some(task);
+        }
+        let len = self.workers.len();
+        for i in 1..len {
+            let victim = &self.workers[(idx + i) % len];
+            if let Some(task) = victim.steal_into(&self.workers[idx]) {
+                return Some(task);
+            }
+        }
+        None
+    }
+
+    // Block until a task is available for worker `idx`, or return `None` once
+    // the pool has been closed or shut down.
+    fn next_task(&self, idx: usize) -> Option<Task> {
+        loop {
+            let task = match self.find_task(idx) {
+                Some(task) => task,
+                None => self.wait_for_task(idx)?,
+            };
+            if self.is_cancelled() {
+                self.cancel_task(task);
+                continue;
+            }
+            return Some(task);
+        }
+    }
+
+    fn wait_for_task(&self, idx: usize) -> Option<Task> {
+        let mut guard = self.sleep.lock().unwrap();
+        self.num_sleeping.fetch_add(1, Ordering::SeqCst);
+        let task = loop {
+            // Re-check with `num_sleeping` raised so that a concurrent
+            // `schedule` cannot slip in between the check and the wait.
+            if let Some(task) = self.find_task(idx) {
+                break Some(task);
+            }
+            if self.is_done() {
+                break None;
+            }
+            guard = self.sleep_cv.wait(guard).unwrap();
+        };
+        self.num_sleeping.fetch_sub(1, Ordering::SeqCst);
+        task
     }
 
     fn work(&self,
             idx: usize,
-            after_start: Option<Arc<dyn Fn(usize) + Send + Sync>>,
-            before_stop: Option<Arc<dyn Fn(usize) + Send + Sync>>) {
+            after_start: Option<WorkerHook>,
+            before_stop: Option<WorkerHook>,
+            in_task: &Cell<bool>) {
         let _scope = enter().unwrap();
+        CURRENT_WORKER.with(|current| current.set(Some((self.id(), idx))));
         if let Some(after_start) = after_start {
             after_start(idx);
         }
-        loop {
-            let msg = self.rx.lock().unwrap().recv().unwrap();
-            match msg {
-                Message::Run(task) => task.run(),
-                Message::Close => break,
-            }
+        while let Some(task) = self.next_task(idx) {
+            in_task.set(true);
+            task.run(idx);
+            in_task.set(false);
         }
         if let Some(before_stop) = before_stop {
             before_stop(idx);
         }
+        CURRENT_WORKER.with(|current| current.set(None));
+        self.worker_exited();
+    }
+
+    fn worker_exited(&self) {
+        let mut running = self.running.lock().unwrap();
+        *running -= 1;
+        if *running == 0 {
+            self.running_cv.notify_all();
+        }
+    }
+}
+
+// Spawn the worker thread with index `idx`.
+fn spawn_worker(
+    state: &Arc<PoolState>,
+    idx: usize,
+    after_start: Option<WorkerHook>,
+    before_stop: Option<WorkerHook>,
+) -> io::Result<()> {
+    let mut thread_builder = thread::Builder::new();
+    if let Some(ref name_prefix) = state.name_prefix {
+        thread_builder = thread_builder.name(format!("{}{}", name_prefix, idx));
+    }
+    if state.stack_size > 0 {
+        thread_builder = thread_builder.stack_size(state.stack_size);
+    }
+
+    let state2 = state.clone();
+    *state.running.lock().unwrap() += 1;
+    let res = thread_builder.spawn(move || {
+        let replace = ReplaceOnPanic {
+            state: &state2,
+            idx,
+            hooks: (after_start.clone(), before_stop.clone()),
+            in_task: Cell::new(false),
+        };
+        state2.work(idx, after_start, before_stop, &replace.in_task)
+    });
+    match res {
+        Ok(thread) => {
+            state.threads.lock().unwrap().push(thread);
+            Ok(())
+        }
+        Err(e) => {
+            state.worker_exited();
+            Err(e)
+        }
+    }
+}
+
+// Replaces a worker thread which is unwinding because of a task, so that the
+// pool keeps its configured number of workers.
+struct ReplaceOnPanic<'a> {
+    state: &'a Arc<PoolState>,
+    idx: usize,
+    // The worker hooks, for the replacement to run too
+    hooks: (Option<WorkerHook>, Option<WorkerHook>),
+    // Whether the worker is running a task, rather than one of its hooks
+    in_task: Cell<bool>,
+}
+
+impl Drop for ReplaceOnPanic<'_> {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            return;
+        }
+        // A worker hook which panicked would panic again in the replacement,
+        // and a pool which is shutting down waits for its workers to exit.
+        let stopping = self.state.closed.load(Ordering::SeqCst) ||
+            self.state.shutdown.load(Ordering::SeqCst);
+        if self.in_task.get() && !stopping {
+            let (after_start, before_stop) = self.hooks.clone();
+            // Spawn the replacement before unregistering this worker, so
+            // that the number of running workers does not drop to zero in
+            // between. If spawning fails there is nothing more to do; the
+            // pool runs on with one less worker.
+            let _ = spawn_worker(self.state, self.idx, after_start, before_stop);
+        }
+        self.state.worker_exited();
+    }
+}
+
+impl WorkerQueue {
+    fn new() -> WorkerQueue {
+        WorkerQueue {
+            tasks: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn push(&self, task: Task) {
+        self.tasks.lock().unwrap().push_back(task);
+    }
+
+    fn pop(&self) -> Option<Task> {
+        self.tasks.lock().unwrap().pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.lock().unwrap().len()
+    }
+
+    // Move half of this queue onto `dest`, returning one of the stolen tasks
+    // to run immediately.
+    fn steal_into(&self, dest: &WorkerQueue) -> Option<Task> {
+        let mut stolen = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let len = tasks.len();
+            if len == 0 {
+                return None;
+            }
+            tasks.split_off(len - (len + 1) / 2)
+        };
+        let task = stolen.pop_front();
+        if !stolen.is_empty() {
+            dest.tasks.lock().unwrap().extend(stolen);
+        }
+        task
     }
 }
 
@@ -162,9 +845,7 @@ impl Clone for ThreadPool {
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         if self.state.cnt.fetch_sub(1, Ordering::Relaxed) == 1 {
-            for _ in 0..self.state.size {
-                self.state.send(Message::Close);
-            }
+            self.state.close();
         }
     }
 }
@@ -180,6 +861,12 @@ impl ThreadPoolBuilder {
             name_prefix: None,
             after_start: None,
             before_stop: None,
+            hooks: TaskHooks::default(),
+            shutdown_policy: ShutdownPolicy::Drain,
+            panic_policy: PanicPolicy::Catch,
+            panic_handler: None,
+            max_blocking_threads: 512,
+            blocking_keep_alive: Duration::from_secs(10),
         }
     }
 
@@ -216,7 +903,9 @@ impl ThreadPoolBuilder {
     ///
     /// This hook is intended for bookkeeping and monitoring.
     /// The closure `f` will be dropped after the `builder` is dropped
-    /// and all worker threads in the pool have executed it.
+    /// and all worker threads in the pool have exited, as the workers
+    /// replacing panicked ones execute it too. A worker whose hook panics is
+    /// not replaced.
     ///
     /// The closure provided will receive an index corresponding to the worker
     /// thread it's running on.
@@ -231,7 +920,9 @@ impl ThreadPoolBuilder {
     ///
     /// This hook is intended for bookkeeping and monitoring.
     /// The closure `f` will be dropped after the `builder` is droppped
-    /// and all threads in the pool have executed it.
+    /// and all threads in the pool have executed it. The workers replacing
+    /// panicked ones execute it too. A worker whose hook panics is not
+    /// replaced.
     ///
     /// The closure provided will receive an index corresponding to the worker
     /// thread it's running on.
@@ -242,35 +933,172 @@ impl ThreadPoolBuilder {
         self
     }
 
+    /// Execute the closure `f` whenever a task is spawned onto the pool.
+    ///
+    /// The closure runs on the spawning thread, before the task is first
+    /// scheduled.
+    pub fn on_task_spawn<F>(&mut self, f: F) -> &mut Self
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.hooks.on_task_spawn = Some(Arc::new(f));
+        self
+    }
+
+    /// Execute the closure `f` on a worker thread just before it polls a
+    /// task.
+    ///
+    /// The closure provided will receive an index corresponding to the worker
+    /// thread it's running on. The id of the task is available through
+    /// [`TaskId::current`](futures_util::task::TaskId::current).
+    pub fn before_poll<F>(&mut self, f: F) -> &mut Self
+        where F: Fn(usize) + Send + Sync + 'static
+    {
+        self.hooks.before_poll = Some(Arc::new(f));
+        self
+    }
+
+    /// Execute the closure `f` on a worker thread just after it has polled a
+    /// task.
+    ///
+    /// The closure provided will receive an index corresponding to the worker
+    /// thread it's running on, and how long the poll took. Long polls are
+    /// usually a sign of a task blocking its worker thread; the id of the
+    /// task is available through
+    /// [`TaskId::current`](futures_util::task::TaskId::current).
+    pub fn after_poll<F>(&mut self, f: F) -> &mut Self
+        where F: Fn(usize, Duration) + Send + Sync + 'static
+    {
+        self.hooks.after_poll = Some(Arc::new(f));
+        self
+    }
+
+    /// Execute the closure `f` on a worker thread whenever a task completes.
+    ///
+    /// The closure provided will receive an index corresponding to the worker
+    /// thread it's running on. Tasks dropped by
+    /// [`ShutdownPolicy::Cancel`](ShutdownPolicy::Cancel) do not complete
+    /// and are not reported.
+    pub fn on_task_complete<F>(&mut self, f: F) -> &mut Self
+        where F: Fn(usize) + Send + Sync + 'static
+    {
+        self.hooks.on_task_complete = Some(Arc::new(f));
+        self
+    }
+
+    /// Set what happens to the tasks remaining on the pool when
+    /// [`ThreadPool::shutdown`](ThreadPool::shutdown) is called.
+    ///
+    /// By default, this is [`ShutdownPolicy::Drain`](ShutdownPolicy::Drain):
+    /// shutting down waits for every spawned task to complete.
+    pub fn shutdown_policy(&mut self, policy: ShutdownPolicy) -> &mut Self {
+        self.shutdown_policy = policy;
+        self
+    }
+
+    /// Set what happens when polling a task panics.
+    ///
+    /// By default, this is [`PanicPolicy::Catch`](PanicPolicy::Catch): the
+    /// panicking task is dropped and the worker thread moves on to other
+    /// tasks. Either way, the pool keeps
+    /// [`pool_size`](ThreadPoolBuilder::pool_size) worker threads running
+    /// until it is shut down, unless a worker hook like
+    /// [`after_start`](ThreadPoolBuilder::after_start) panics.
+    ///
+    /// A panic in one of the per-task hooks, like
+    /// [`on_task_complete`](ThreadPoolBuilder::on_task_complete), is handled
+    /// like a panic of the task it was called for.
+    ///
+    /// Tasks spawned with
+    /// [`ThreadPool::spawn_with_join_handle`](ThreadPool::spawn_with_join_handle)
+    /// report their panics through their handle instead, and are not
+    /// affected by this policy.
+    pub fn panic_policy(&mut self, policy: PanicPolicy) -> &mut Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Execute the closure `f` whenever a task panics, with the name of the
+    /// task, if it has one, and the panic payload.
+    ///
+    /// The closure runs on the worker thread which caught the panic, and is
+    /// only used with [`PanicPolicy::Catch`](PanicPolicy::Catch).
+    pub fn panic_handler<F>(&mut self, f: F) -> &mut Self
+        where F: Fn(Option<&str>, Box<dyn Any + Send>) + Send + Sync + 'static
+    {
+        self.panic_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Set the maximum number of threads used to run closures passed to
+    /// [`ThreadPool::spawn_blocking`](ThreadPool::spawn_blocking).
+    ///
+    /// Blocking threads are separate from the worker threads counted by
+    /// [`pool_size`](ThreadPoolBuilder::pool_size) and are only spawned when
+    /// needed. By default, at most 512 blocking threads are used.
+    ///
+    /// # Panics
+    ///
+    /// [`create`](ThreadPoolBuilder::create) panics if `max == 0`.
+    pub fn max_blocking_threads(&mut self, max: usize) -> &mut Self {
+        self.max_blocking_threads = max;
+        self
+    }
+
+    /// Set how long a blocking thread may stay idle before exiting.
+    ///
+    /// By default, blocking threads exit after 10 seconds without work.
+    pub fn blocking_keep_alive(&mut self, keep_alive: Duration) -> &mut Self {
+        self.blocking_keep_alive = keep_alive;
+        self
+    }
+
     /// Create a [`ThreadPool`](ThreadPool) with the given configuration.
     ///
     /// # Panics
     ///
-    /// Panics if `pool_size == 0`.
+    /// Panics if `pool_size == 0` or `max_blocking_threads == 0`.
     pub fn create(&mut self) -> Result<ThreadPool, io::Error> {
-        let (tx, rx) = mpsc::channel();
+        assert!(self.pool_size > 0);
+        assert!(self.max_blocking_threads > 0);
         let pool = ThreadPool {
             state: Arc::new(PoolState {
-                tx: Mutex::new(tx),
-                rx: Mutex::new(rx),
+                injector: Mutex::new(VecDeque::new()),
+                workers: (0..self.pool_size).map(|_| WorkerQueue::new()).collect(),
+                sleep: Mutex::new(()),
+                sleep_cv: Condvar::new(),
+                num_sleeping: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                shutdown: AtomicBool::new(false),
+                shutdown_policy: self.shutdown_policy,
+                num_tasks: AtomicUsize::new(0),
+                tasks: Mutex::new(HashMap::new()),
+                running: Mutex::new(0),
+                running_cv: Condvar::new(),
+                threads: Mutex::new(Vec::with_capacity(self.pool_size)),
+                panic_policy: self.panic_policy,
+                panic_handler: self.panic_handler.clone(),
+                stack_size: self.stack_size,
+                name_prefix: self.name_prefix.clone(),
+                blocking: BlockingPool::new(
+                    self.max_blocking_threads,
+                    self.blocking_keep_alive,
+                    self.stack_size,
+                    self.name_prefix.clone(),
+                ),
+                hooks: self.hooks.clone(),
+                counters: Counters::default(),
                 cnt: AtomicUsize::new(1),
                 size: self.pool_size,
             }),
         };
-        assert!(self.pool_size > 0);
 
         for counter in 0..self.pool_size {
-            let state = pool.state.clone();
-            let after_start = self.after_start.clone();
-            let before_stop = self.before_stop.clone();
-            let mut thread_builder = thread::Builder::new();
-            if let Some(ref name_prefix) = self.name_prefix {
-                thread_builder = thread_builder.name(format!("{}{}", name_prefix, counter));
-            }
-            if self.stack_size > 0 {
-                thread_builder = thread_builder.stack_size(self.stack_size);
-            }
-            thread_builder.spawn(move || state.work(counter, after_start, before_stop))?;
+            spawn_worker(
+                &pool.state,
+                counter,
+                self.after_start.clone(),
+                self.before_stop.clone(),
+            )?;
         }
         Ok(pool)
     }
@@ -292,12 +1120,13 @@ struct Task {
 struct WakeHandle {
     mutex: UnparkMutex<Task>,
     exec: ThreadPool,
+    meta: TaskMeta,
 }
 
 impl Task {
     /// Actually run the task (invoking `poll` on the future) on the current
     /// thread.
-    pub fn run(self) {
+    pub fn run(self, idx: usize) {
         let Task { mut future, wake_handle, mut exec } = self;
         let waker = waker_ref(&wake_handle);
         let mut cx = Context::from_waker(&waker);
@@ -308,10 +1137,25 @@ impl Task {
             wake_handle.mutex.start_poll();
 
             loop {
-                let res = future.poll_unpin(&mut cx);
+                let res = exec.state.poll_task(idx, &wake_handle.meta, &mut future, &mut cx);
                 match res {
-                    Poll::Pending => {}
-                    Poll::Ready(()) => return wake_handle.mutex.complete(),
+                    Ok(Poll::Pending) => {}
+                    Err(payload) => {
+                        wake_handle.mutex.complete();
+                        drop(future);
+                        return exec.state.task_panicked(&wake_handle.meta, payload);
+                    }
+                    Ok(Poll::Ready(())) => {
+                        wake_handle.mutex.complete();
+                        if let Some(ref on_task_complete) = exec.state.hooks.on_task_complete {
+                            let hook = || on_task_complete(idx);
+                            if !exec.state.run_task_hook(&wake_handle.meta, hook) {
+                                return;
+                            }
+                        }
+                        exec.state.unregister(&wake_handle.meta);
+                        return exec.state.task_done();
+                    }
                 }
                 let task = Task {
                     future,
@@ -319,8 +1163,20 @@ impl Task {
                     exec,
                 };
                 match wake_handle.mutex.wait(task) {
-                    Ok(()) => return, // we've waited
+                    Ok(()) => { // we've waited
+                        // The pool may have been shut down while this task
+                        // was being polled, missing it while sweeping the
+                        // waiting tasks.
+                        if wake_handle.exec.state.is_cancelled() {
+                            WakeHandle::wake_by_ref(&wake_handle);
+                        }
+                        return;
+                    }
                     Err(task) => { // someone's notified us
+                        if wake_handle.exec.state.is_cancelled() {
+                            wake_handle.mutex.complete();
+                            return wake_handle.exec.state.cancel_task(task);
+                        }
                         future = task.future;
                         exec = task.exec;
                     }
@@ -333,6 +1189,7 @@ impl Task {
 impl fmt::Debug for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Task")
+            .field("meta", &self.wake_handle.meta)
             .field("contents", &"...")
             .finish()
     }
@@ -341,7 +1198,7 @@ impl fmt::Debug for Task {
 impl ArcWake for WakeHandle {
     fn wake_by_ref(arc_self: &Arc<Self>) {
         match arc_self.mutex.notify() {
-            Ok(task) => arc_self.exec.state.send(Message::Run(task)),
+            Ok(task) => arc_self.exec.state.schedule(task),
             Err(()) => {}
         }
     }
@@ -355,12 +1212,14 @@ mod tests {
     #[test]
     fn test_drop_after_start() {
         let (tx, rx) = mpsc::sync_channel(2);
-        let _cpu_pool = ThreadPoolBuilder::new()
+        let cpu_pool = ThreadPoolBuilder::new()
             .pool_size(2)
             .after_start(move |_| tx.send(1).unwrap()).create().unwrap();
 
-        // After ThreadPoolBuilder is deconstructed, the tx should be droped
-        // so that we can use rx as an iterator.
+        // After ThreadPoolBuilder is deconstructed and the worker threads
+        // have exited, the tx should be droped so that we can use rx as an
+        // iterator. The workers keep the hook for their replacements.
+        drop(cpu_pool);
         let count = rx.into_iter().count();
         assert_eq!(count, 2);
     }
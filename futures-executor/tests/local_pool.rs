@@ -2,7 +2,7 @@
 
 use futures::channel::oneshot;
 use futures::executor::LocalPool;
-use futures::future::{Future, lazy};
+use futures::future::{Future, FutureExt, lazy, poll_fn};
 use futures::task::{Context, Poll, Spawn, LocalSpawn};
 use std::cell::{Cell, RefCell};
 use std::pin::Pin;
@@ -167,3 +167,233 @@ fn tasks_are_scheduled_fairly() {
 
     pool.run();
 }
+
+#[test]
+fn join_handle_output() {
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    let value = Rc::new(5);
+    let handle = spawn.spawn_local_with_join_handle(lazy(move |_| *value + 1)).unwrap();
+    assert_eq!(pool.run_until(handle).unwrap(), 6);
+}
+
+#[test]
+fn join_handle_detach_keeps_running() {
+    let cnt = Rc::new(Cell::new(0));
+    let cnt2 = cnt.clone();
+
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    let handle = spawn.spawn_local_with_join_handle(lazy(move |_| {
+        cnt2.set(cnt2.get() + 1);
+    })).unwrap();
+    assert!(!handle.is_finished());
+    drop(handle);
+
+    pool.run();
+    assert_eq!(cnt.get(), 1);
+}
+
+#[test]
+fn join_handle_abort() {
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    let handle = spawn.spawn_local_with_join_handle(pending()).unwrap();
+    handle.abort();
+    pool.run();
+    assert!(handle.is_finished());
+    assert!(pool.run_until(handle).unwrap_err().is_cancelled());
+}
+
+#[test]
+fn join_handle_cancelled_when_pool_dropped() {
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    let handle = spawn.spawn_local_with_join_handle(pending()).unwrap();
+    drop(pool);
+    assert!(handle.is_finished());
+    assert!(futures::executor::block_on(handle).unwrap_err().is_cancelled());
+}
+
+#[test]
+fn try_run_one_completes_one_task() {
+    let cnt = Rc::new(Cell::new(0));
+
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    for _ in 0..3 {
+        let cnt = cnt.clone();
+        spawn.spawn_local_obj(Box::pin(lazy(move |_| {
+            cnt.set(cnt.get() + 1);
+        })).into()).unwrap();
+    }
+    assert_eq!(pool.num_tasks(), 3);
+
+    assert!(pool.try_run_one());
+    assert_eq!(cnt.get(), 1);
+    assert_eq!(pool.num_tasks(), 2);
+    assert!(pool.try_run_one());
+    assert!(pool.try_run_one());
+    assert_eq!(cnt.get(), 3);
+    assert!(!pool.try_run_one());
+    assert_eq!(pool.num_tasks(), 0);
+}
+
+#[test]
+fn try_run_one_returns_on_stall() {
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    spawn.spawn_local_obj(Box::pin(pending()).into()).unwrap();
+    // the task is polled once, and then waits to be woken
+    assert!(pool.try_run_one());
+    assert!(!pool.try_run_one());
+    assert_eq!(pool.num_tasks(), 1);
+}
+
+#[test]
+fn try_run_one_polls_one_task() {
+    let polls = Rc::new(Cell::new(0));
+
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    for _ in 0..3 {
+        let polls = polls.clone();
+        spawn.spawn_local_obj(Box::pin(poll_fn(move |cx| {
+            polls.set(polls.get() + 1);
+            cx.waker().wake_by_ref();
+            Poll::<()>::Pending
+        })).into()).unwrap();
+    }
+
+    for i in 1..=6 {
+        assert!(pool.try_run_one());
+        assert_eq!(polls.get(), i);
+    }
+}
+
+#[test]
+fn run_until_stalled_returns_with_pending_tasks() {
+    let (tx, rx) = oneshot::channel();
+    let cnt = Rc::new(Cell::new(0));
+    let cnt2 = cnt.clone();
+
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+    let mut spawn2 = pool.spawner();
+
+    spawn.spawn_local_obj(Box::pin(pending()).into()).unwrap();
+    spawn.spawn_local_obj(Box::pin(lazy(move |_| {
+        spawn2.spawn_local_obj(Box::pin(rx.map(move |_| {
+            cnt2.set(cnt2.get() + 1);
+        })).into()).unwrap();
+    })).into()).unwrap();
+
+    pool.run_until_stalled();
+    assert_eq!(cnt.get(), 0);
+    assert_eq!(pool.num_tasks(), 2);
+
+    tx.send(()).unwrap();
+    pool.run_until_stalled();
+    assert_eq!(cnt.get(), 1);
+    assert_eq!(pool.num_tasks(), 1);
+}
+
+#[test]
+fn run_until_stalled_runs_self_waking_task() {
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    struct Yield(usize);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                Poll::Ready(())
+            } else {
+                self.0 -= 1;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    spawn.spawn_local_obj(Box::pin(Yield(10)).into()).unwrap();
+    pool.run_until_stalled();
+    assert_eq!(pool.num_tasks(), 0);
+}
+
+#[test]
+fn metrics() {
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    spawn.spawn_local_obj(Box::pin(pending()).into()).unwrap();
+    spawn.spawn_local_obj(Box::pin(lazy(|_| ())).into()).unwrap();
+    let metrics = pool.metrics();
+    assert_eq!(metrics.tasks_spawned(), 2);
+    assert_eq!(metrics.tasks_alive(), 2);
+    assert_eq!(metrics.total_polls(), 0);
+
+    pool.run_until_stalled();
+    let metrics = pool.metrics();
+    assert_eq!(metrics.tasks_spawned(), 2);
+    assert_eq!(metrics.tasks_alive(), 1);
+    assert_eq!(metrics.total_polls(), 2);
+    assert_eq!(metrics.poll_duration_histogram().iter().sum::<usize>(), 2);
+    assert!(metrics.queue_depths().is_empty());
+    assert_eq!(metrics.injector_depth(), 0);
+}
+
+#[test]
+fn task_names_are_reported() {
+    use futures::task::{LocalSpawnExt, TaskOptions};
+
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+    spawn.build_local_task().name("b").spawn_local(Pending(Rc::new(()))).unwrap();
+    spawn.build_local_task().name("a").spawn_local(Pending(Rc::new(()))).unwrap();
+    spawn.spawn_local(Pending(Rc::new(()))).unwrap();
+    assert_eq!(format!("{:?}", pool), r#"LocalPool { num_tasks: 3, task_names: ["b", "a"] }"#);
+    assert_eq!(pool.metrics().task_names(), ["a", "b"]);
+
+    let options = TaskOptions::new().with_name("panicky");
+    let handle = spawn.spawn_local_with_join_handle_with(lazy(|_| panic!("boom")), &options)
+        .unwrap();
+    let err = pool.run_until(handle).unwrap_err();
+    assert_eq!(err.name(), Some("panicky"));
+    assert_eq!(err.to_string(), "task 'panicky' panicked");
+}
+
+#[test]
+fn named_tasks_get_distinct_ids() {
+    use futures::task::{LocalSpawnExt, SpawnExt, TaskId};
+
+    let ids = Rc::new(RefCell::new(Vec::new()));
+    let mut pool = LocalPool::new();
+    let mut spawn = pool.spawner();
+
+    let (ids1, ids2) = (ids.clone(), ids.clone());
+    spawn.build_task().name("first").spawn_local(lazy(move |_| {
+        ids1.borrow_mut().push(TaskId::current().unwrap());
+    })).unwrap();
+    spawn.build_local_task().spawn_local(lazy(move |_| {
+        ids2.borrow_mut().push(TaskId::current().unwrap());
+    })).unwrap();
+    let handle = spawn.spawn_with_handle(lazy(|_| TaskId::current())).unwrap();
+    let id3 = pool.run_until(handle).unwrap();
+
+    let ids = ids.borrow();
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+    assert!(!ids.contains(&id3));
+    assert_eq!(TaskId::current(), None);
+}
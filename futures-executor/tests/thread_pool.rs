@@ -0,0 +1,457 @@
+#![feature(futures_api)]
+
+use futures::channel::{mpsc, oneshot};
+use futures::executor::{block_on, PanicPolicy, ShutdownPolicy, ThreadPool};
+use futures::future::{self, lazy, FutureExt};
+use futures::stream::StreamExt;
+use futures::task::{Spawn, SpawnExt};
+use std::cmp;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn run_spawn_many() {
+    const ITER: usize = 1000;
+
+    let cnt = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::unbounded();
+    let mut pool = ThreadPool::builder().pool_size(4).create().unwrap();
+
+    for _ in 0..ITER {
+        let cnt = cnt.clone();
+        let tx = tx.clone();
+        pool.spawn(lazy(move |_| {
+            cnt.fetch_add(1, Ordering::SeqCst);
+            tx.unbounded_send(()).unwrap();
+        })).unwrap();
+    }
+    drop(tx);
+
+    assert_eq!(block_on(rx.collect::<Vec<_>>()).len(), ITER);
+    assert_eq!(cnt.load(Ordering::SeqCst), ITER);
+}
+
+#[test]
+fn spawn_from_worker() {
+    const ITER: usize = 100;
+
+    let (tx, rx) = mpsc::unbounded();
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+    let mut spawner = pool.clone();
+
+    pool.spawn(lazy(move |_| {
+        // Tasks spawned here land on the local queue of this worker and
+        // must still be picked up, either locally or by stealing.
+        for i in 0..ITER {
+            let tx = tx.clone();
+            spawner.spawn(lazy(move |_| tx.unbounded_send(i).unwrap())).unwrap();
+        }
+    })).unwrap();
+
+    let mut items = block_on(rx.collect::<Vec<_>>());
+    items.sort();
+    assert_eq!(items, (0..ITER).collect::<Vec<_>>());
+}
+
+#[test]
+fn wake_from_other_thread() {
+    let (tx, rx) = oneshot::channel::<u32>();
+    let (done_tx, done_rx) = oneshot::channel();
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+
+    pool.spawn(rx.map(move |v| {
+        done_tx.send(v.unwrap()).unwrap();
+    })).unwrap();
+
+    tx.send(7).unwrap();
+    assert_eq!(block_on(done_rx).unwrap(), 7);
+}
+
+#[test]
+fn join_handle_output() {
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+    let handle = pool.spawn_with_join_handle(lazy(|_| 42)).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 42);
+}
+
+#[test]
+fn join_handle_detach() {
+    let (tx, rx) = oneshot::channel::<()>();
+    let (done_tx, done_rx) = oneshot::channel();
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+
+    let handle = pool.spawn_with_join_handle(rx.map(move |_| {
+        done_tx.send(()).unwrap();
+    })).unwrap();
+    assert!(!handle.is_finished());
+    handle.detach();
+
+    tx.send(()).unwrap();
+    block_on(done_rx).unwrap();
+}
+
+#[test]
+fn join_handle_abort() {
+    let (_tx, rx) = oneshot::channel::<()>();
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+
+    let handle = pool.spawn_with_join_handle(rx).unwrap();
+    handle.abort();
+    assert!(block_on(handle).unwrap_err().is_cancelled());
+}
+
+#[test]
+fn join_handle_panic() {
+    let mut pool = ThreadPool::builder().pool_size(1).create().unwrap();
+
+    let handle = pool.spawn_with_join_handle(lazy(|_| -> u32 {
+        panic!("boom")
+    })).unwrap();
+    let err = block_on(handle).unwrap_err();
+    assert!(err.is_panic());
+    assert_eq!(*err.try_into_panic().unwrap().downcast::<&str>().unwrap(), "boom");
+
+    // the only worker thread survived the panic
+    let handle = pool.spawn_with_join_handle(lazy(|_| 1)).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 1);
+}
+
+#[test]
+fn shutdown_rejects_new_tasks() {
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+    pool.shutdown();
+
+    assert!(pool.status().unwrap_err().is_shutdown());
+    assert!(pool.spawn(lazy(|_| ())).unwrap_err().is_shutdown());
+}
+
+#[test]
+fn shutdown_drains_tasks() {
+    const ITER: usize = 100;
+
+    let cnt = Arc::new(AtomicUsize::new(0));
+    let stopped = Arc::new(AtomicUsize::new(0));
+    let stopped2 = stopped.clone();
+    let mut pool = ThreadPool::builder()
+        .pool_size(2)
+        .before_stop(move |_| { stopped2.fetch_add(1, Ordering::SeqCst); })
+        .create()
+        .unwrap();
+
+    for _ in 0..ITER {
+        let cnt = cnt.clone();
+        pool.spawn(lazy(move |_| { cnt.fetch_add(1, Ordering::SeqCst); })).unwrap();
+    }
+    pool.shutdown();
+
+    assert_eq!(cnt.load(Ordering::SeqCst), ITER);
+    assert_eq!(stopped.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn shutdown_timeout_waits_for_pending_task() {
+    let (tx, rx) = oneshot::channel::<()>();
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+    let handle = pool.spawn_with_join_handle(rx).unwrap();
+
+    assert!(!pool.shutdown_timeout(Duration::from_millis(50)));
+    assert!(!handle.is_finished());
+
+    tx.send(()).unwrap();
+    assert!(pool.shutdown_timeout(Duration::from_secs(10)));
+    assert!(block_on(handle).unwrap().is_ok());
+}
+
+#[test]
+fn shutdown_cancels_pending_tasks() {
+    let (tx, rx) = oneshot::channel::<()>();
+    let mut pool = ThreadPool::builder()
+        .pool_size(2)
+        .shutdown_policy(ShutdownPolicy::Cancel)
+        .create()
+        .unwrap();
+    let (started_tx, started_rx) = std::sync::mpsc::channel();
+    let task = lazy(move |_| started_tx.send(()).unwrap()).then(|()| rx);
+    let handle = pool.spawn_with_join_handle(task).unwrap();
+    // wait for the task to be parked on `rx`
+    started_rx.recv().unwrap();
+    thread::sleep(Duration::from_millis(20));
+    pool.shutdown();
+
+    // the task is dropped without being woken up
+    assert!(block_on(handle).unwrap_err().is_cancelled());
+    assert!(tx.is_canceled());
+}
+
+#[test]
+fn spawn_blocking_output() {
+    let mut pool = ThreadPool::builder().pool_size(1).create().unwrap();
+    let spawner = pool.clone();
+
+    // the blocking closure runs off the single worker thread, which keeps
+    // polling the task waiting on it
+    let handle = pool.spawn_with_join_handle(lazy(move |_| {
+        spawner.spawn_blocking(|| {
+            thread::sleep(Duration::from_millis(10));
+            5
+        })
+    }).flatten()).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 5);
+}
+
+#[test]
+fn spawn_blocking_respects_max_threads() {
+    let pool = ThreadPool::builder()
+        .pool_size(1)
+        .max_blocking_threads(2)
+        .create()
+        .unwrap();
+    // (currently running, most ever running at once)
+    let running = Arc::new(Mutex::new((0, 0)));
+
+    let tasks = (0..6).map(|_| {
+        let running = running.clone();
+        pool.spawn_blocking(move || {
+            {
+                let mut running = running.lock().unwrap();
+                running.0 += 1;
+                running.1 = cmp::max(running.0, running.1);
+            }
+            thread::sleep(Duration::from_millis(10));
+            running.lock().unwrap().0 -= 1;
+        })
+    }).collect::<Vec<_>>();
+    block_on(future::join_all(tasks));
+
+    assert!(running.lock().unwrap().1 <= 2);
+}
+
+#[test]
+fn spawn_blocking_back_to_back_with_idle_thread() {
+    // whether both jobs are pushed before the idle thread wakes up is racy,
+    // so try a few times
+    for _ in 0..10 {
+        let pool = ThreadPool::builder().pool_size(1).create().unwrap();
+        // leave a single idle blocking thread behind
+        block_on(pool.spawn_blocking(|| ()));
+        thread::sleep(Duration::from_millis(20));
+
+        // the first job can only finish once the second one has run, so they
+        // need a thread each
+        let (tx, rx) = std::sync::mpsc::channel();
+        let first = pool.spawn_blocking(move || rx.recv_timeout(Duration::from_secs(1)).is_ok());
+        let second = pool.spawn_blocking(move || tx.send(()).unwrap());
+        assert!(block_on(first));
+        block_on(second);
+    }
+}
+
+#[test]
+fn spawn_blocking_panic() {
+    let pool = ThreadPool::builder().pool_size(1).create().unwrap();
+    let fut = pool.spawn_blocking(|| -> u32 { panic!("boom") });
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| block_on(fut))).is_err());
+
+    // the blocking thread survived the panic
+    assert_eq!(block_on(pool.spawn_blocking(|| 1)), 1);
+}
+
+#[test]
+fn hooks_and_metrics() {
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let polls = Arc::new(AtomicUsize::new(0));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let (spawned2, before, after, completed2) =
+        (spawned.clone(), polls.clone(), polls.clone(), completed.clone());
+    let mut pool = ThreadPool::builder()
+        .pool_size(2)
+        .on_task_spawn(move || { spawned2.fetch_add(1, Ordering::SeqCst); })
+        .before_poll(move |_| { before.fetch_add(1, Ordering::SeqCst); })
+        .after_poll(move |_, _| { after.fetch_add(1, Ordering::SeqCst); })
+        .on_task_complete(move |_| { completed2.fetch_add(1, Ordering::SeqCst); })
+        .create()
+        .unwrap();
+
+    let (tx, rx) = oneshot::channel::<()>();
+    let waiting = pool.spawn_with_join_handle(rx).unwrap();
+    let ready = pool.spawn_with_join_handle(future::ready(())).unwrap();
+    block_on(ready).unwrap();
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.tasks_spawned(), 2);
+    assert_eq!(metrics.queue_depths().len(), 2);
+
+    tx.send(()).unwrap();
+    block_on(waiting).unwrap().unwrap();
+    pool.shutdown();
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.tasks_spawned(), 2);
+    assert_eq!(metrics.tasks_alive(), 0);
+    assert_eq!(metrics.injector_depth(), 0);
+    assert!(metrics.total_polls() >= 3);
+    assert_eq!(metrics.poll_duration_histogram().iter().sum::<usize>(),
+               metrics.total_polls());
+    assert_eq!(spawned.load(Ordering::SeqCst), 2);
+    // each poll runs both poll hooks
+    assert_eq!(polls.load(Ordering::SeqCst), 2 * metrics.total_polls());
+    assert_eq!(completed.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn named_task_id() {
+    use futures::task::TaskId;
+
+    let mut pool = ThreadPool::builder().pool_size(1).create().unwrap();
+    let (tx, rx) = oneshot::channel();
+    pool.build_task().name("conn-42").spawn(lazy(move |_| {
+        tx.send(TaskId::current()).unwrap();
+    })).unwrap();
+    let id = block_on(rx).unwrap();
+    assert!(id.is_some());
+
+    let handle = pool.spawn_with_join_handle(lazy(|_| TaskId::current())).unwrap();
+    let other = block_on(handle).unwrap();
+    assert!(other.is_some());
+    assert_ne!(id, other);
+}
+
+#[test]
+fn task_names_are_reported() {
+    use futures::task::TaskOptions;
+
+    let (tx, rx) = mpsc::unbounded();
+    let tx = Mutex::new(tx);
+    let mut pool = ThreadPool::builder()
+        .pool_size(1)
+        .panic_handler(move |name, _payload| {
+            tx.lock().unwrap().unbounded_send(name.map(String::from)).unwrap();
+        })
+        .create()
+        .unwrap();
+
+    pool.build_task().name("conn-42").spawn(lazy(|_| panic!("boom"))).unwrap();
+    assert_eq!(block_on(rx.into_future()).0, Some(Some("conn-42".to_string())));
+
+    let (_tx, pending) = oneshot::channel::<()>();
+    let options = TaskOptions::new().with_name("pending");
+    let pending = pool.spawn_with_join_handle_with(pending, &options).unwrap();
+    assert_eq!(pool.metrics().task_names(), ["pending"]);
+    pending.abort();
+    let err = block_on(pending).unwrap_err();
+    assert_eq!(err.name(), Some("pending"));
+    assert_eq!(err.to_string(), "task 'pending' was cancelled");
+
+    let options = TaskOptions::new().with_name("panicky");
+    let handle = pool.spawn_with_join_handle_with(lazy(|_| panic!("boom")), &options).unwrap();
+    let err = block_on(handle).unwrap_err();
+    assert!(err.is_panic());
+    assert_eq!(err.name(), Some("panicky"));
+}
+
+#[test]
+fn panic_policy_catch() {
+    let (tx, rx) = mpsc::unbounded();
+    let tx = Mutex::new(tx);
+    let mut pool = ThreadPool::builder()
+        .pool_size(1)
+        .panic_policy(PanicPolicy::Catch)
+        .panic_handler(move |_name, payload| {
+            let msg = *payload.downcast::<&str>().unwrap();
+            tx.lock().unwrap().unbounded_send(msg).unwrap();
+        })
+        .create()
+        .unwrap();
+
+    pool.spawn(lazy(|_| panic!("boom"))).unwrap();
+    assert_eq!(block_on(rx.into_future()).0, Some("boom"));
+
+    // the worker survived the panic
+    let handle = pool.spawn_with_join_handle(future::ready(1)).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 1);
+    assert!(pool.shutdown_timeout(Duration::from_secs(5)));
+}
+
+#[test]
+fn panic_policy_propagate_replaces_worker() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let started2 = started.clone();
+    let mut pool = ThreadPool::builder()
+        .pool_size(1)
+        .panic_policy(PanicPolicy::Propagate)
+        .after_start(move |_| { started2.fetch_add(1, Ordering::SeqCst); })
+        .create()
+        .unwrap();
+
+    pool.spawn(lazy(|_| panic!("boom"))).unwrap();
+    let handle = pool.spawn_with_join_handle(future::ready(1)).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 1);
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    assert!(pool.shutdown_timeout(Duration::from_secs(5)));
+}
+
+#[test]
+fn panic_policy_catch_covers_hooks() {
+    let (tx, rx) = mpsc::unbounded();
+    let tx = Mutex::new(tx);
+    let mut pool = ThreadPool::builder()
+        .pool_size(1)
+        .panic_policy(PanicPolicy::Catch)
+        .on_task_complete(|_| panic!("hook"))
+        .panic_handler(move |_name, payload| {
+            let msg = *payload.downcast::<&str>().unwrap();
+            tx.lock().unwrap().unbounded_send(msg).unwrap();
+        })
+        .create()
+        .unwrap();
+
+    pool.spawn(future::ready(())).unwrap();
+    assert_eq!(block_on(rx.into_future()).0, Some("hook"));
+    assert!(pool.shutdown_timeout(Duration::from_secs(5)));
+}
+
+#[test]
+fn panic_policy_catch_replacement_runs_hooks() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let started2 = started.clone();
+    let panicked = AtomicUsize::new(0);
+    let mut pool = ThreadPool::builder()
+        .pool_size(1)
+        .panic_policy(PanicPolicy::Catch)
+        .after_start(move |_| { started2.fetch_add(1, Ordering::SeqCst); })
+        // unwinds the worker thread the first time
+        .panic_handler(move |_name, _payload| {
+            if panicked.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("handler");
+            }
+        })
+        .create()
+        .unwrap();
+
+    pool.spawn(lazy(|_| panic!("boom"))).unwrap();
+    let handle = pool.spawn_with_join_handle(future::ready(1)).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 1);
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    assert!(pool.shutdown_timeout(Duration::from_secs(5)));
+}
+
+#[test]
+fn panicking_worker_hook_is_not_replaced() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let started2 = started.clone();
+    let pool = ThreadPool::builder()
+        .pool_size(2)
+        .panic_policy(PanicPolicy::Catch)
+        .after_start(move |_| {
+            started2.fetch_add(1, Ordering::SeqCst);
+            panic!("after_start");
+        })
+        .create()
+        .unwrap();
+
+    assert!(pool.shutdown_timeout(Duration::from_secs(5)));
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+}
@@ -1,5 +1,6 @@
 use crate::{Sink, Poll};
 use futures_core::task::Context;
+use futures_channel::{mpmc, priority};
 use futures_channel::mpsc::{Sender, SendError, TrySendError, UnboundedSender};
 use std::pin::Pin;
 
@@ -66,3 +67,45 @@ impl<'a, T> Sink<T> for &'a UnboundedSender<T> {
         Poll::Ready(Ok(()))
     }
 }
+
+impl<T> Sink<T> for mpmc::Sender<T> {
+    type SinkError = SendError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::SinkError>> {
+        (*self).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, msg: T) -> Result<(), Self::SinkError> {
+        (*self).start_send(msg)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::SinkError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::SinkError>> {
+        self.disconnect();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<P: Ord, T> Sink<(P, T)> for priority::Sender<P, T> {
+    type SinkError = SendError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::SinkError>> {
+        (*self).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, (priority, msg): (P, T)) -> Result<(), Self::SinkError> {
+        (*self).start_send(priority, msg)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::SinkError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::SinkError>> {
+        self.disconnect();
+        Poll::Ready(Ok(()))
+    }
+}
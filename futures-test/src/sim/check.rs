@@ -0,0 +1,69 @@
+use super::SimPool;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The environment variable read by [`check`] to replay a single seed.
+pub const SEED_ENV_VAR: &str = "FUTURES_SIM_SEED";
+
+/// Runs `test` once for each seed in `0..iterations`, each time with a fresh
+/// [`SimPool`](SimPool) created from that seed.
+///
+/// `test` is expected to spawn the tasks under test onto the pool, drive it
+/// (for example with [`run_until_stalled`](SimPool::run_until_stalled)) and
+/// assert on the outcome. If it panics, the failing seed and the recorded
+/// schedule are printed to stderr before the panic is propagated.
+///
+/// Setting the `FUTURES_SIM_SEED` environment variable to a seed reported by
+/// a failing run makes `check` run `test` with only that seed, replaying the
+/// exact same schedule.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(async_await, futures_api)]
+/// use futures::channel::mpsc;
+/// use futures::stream::StreamExt;
+/// use futures::task::LocalSpawnExt;
+/// use futures_test::sim;
+///
+/// sim::check(100, |pool| {
+///     let (tx, rx) = mpsc::unbounded();
+///     let mut spawner = pool.spawner();
+///     for i in 0..3 {
+///         let tx = tx.clone();
+///         spawner.spawn_local(async move { tx.unbounded_send(i).unwrap() }).unwrap();
+///     }
+///     drop(tx);
+///
+///     let mut items = pool.run_until(rx.collect::<Vec<_>>()).unwrap();
+///     items.sort();
+///     assert_eq!(items, vec![0, 1, 2]);
+/// });
+/// ```
+///
+/// # Panics
+///
+/// Panics if `FUTURES_SIM_SEED` is set to something other than an unsigned
+/// integer.
+pub fn check<F>(iterations: u64, mut test: F)
+    where F: FnMut(&mut SimPool)
+{
+    let seeds = match env::var(SEED_ENV_VAR) {
+        Ok(seed) => {
+            let seed = seed.parse::<u64>()
+                .unwrap_or_else(|_| panic!("invalid {}: {:?}", SEED_ENV_VAR, seed));
+            seed..seed + 1
+        }
+        Err(_) => 0..iterations,
+    };
+
+    for seed in seeds {
+        let mut pool = SimPool::new(seed);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| test(&mut pool)));
+        if let Err(payload) = res {
+            eprintln!("simulation failed with seed {} (rerun with {}={}), schedule: {:?}",
+                      seed, SEED_ENV_VAR, seed, pool.schedule());
+            panic::resume_unwind(payload);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Deterministic simulation of task scheduling.
+//!
+//! Bugs that depend on the order in which concurrent tasks happen to be
+//! polled are hard to reproduce with a regular executor, which always polls
+//! tasks in the order they were woken. [`SimPool`] is a single-threaded
+//! executor which instead picks the next task to poll at random, from a
+//! seeded generator, and records the resulting schedule. [`check`] runs a
+//! test against many seeds, and reports the seed of a failing run so that it
+//! can be replayed exactly.
+
+mod check;
+pub use self::check::{check, SEED_ENV_VAR};
+
+mod pool;
+pub use self::pool::{SimPool, SimSpawner};
+
+mod rng;
@@ -0,0 +1,297 @@
+use super::rng::Rng;
+use futures_core::future::{Future, FutureObj, LocalFutureObj};
+use futures_core::task::{Context, LocalSpawn, Poll, Spawn, SpawnError};
+use futures_executor::enter;
+use futures_util::future::FutureExt;
+use futures_util::task::{waker_ref, ArcWake};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A single-threaded executor which polls its tasks in a pseudo-random but
+/// reproducible order.
+///
+/// Whenever several tasks are ready to be polled, `SimPool` picks the next
+/// one using a random number generator initialized from a seed, instead of
+/// polling them in the order they were woken. Running the same deterministic
+/// program with the same seed always yields the same order of polls, which is
+/// recorded and available through [`schedule`](SimPool::schedule). Running it
+/// with many seeds explores many different interleavings; see
+/// [`check`](crate::sim::check).
+///
+/// Tasks are identified by their spawn order: the first task spawned onto the
+/// pool has id `0`, the next one `1`, and so on.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(async_await, futures_api)]
+/// use futures::task::LocalSpawnExt;
+/// use futures_test::sim::SimPool;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let order = Rc::new(RefCell::new(Vec::new()));
+/// let mut pool = SimPool::new(7);
+/// let mut spawner = pool.spawner();
+/// for i in 0..3 {
+///     let order = order.clone();
+///     spawner.spawn_local(async move { order.borrow_mut().push(i) }).unwrap();
+/// }
+/// pool.run_until_stalled();
+///
+/// // the recorded schedule matches the order the tasks ran in
+/// assert_eq!(pool.schedule(), &order.borrow()[..]);
+/// ```
+#[derive(Debug)]
+pub struct SimPool {
+    seed: u64,
+    rng: Rng,
+    // Indexed by task id; `None` once the task has completed
+    tasks: Vec<Option<SimTask>>,
+    num_tasks: usize,
+    ready: Arc<Mutex<Vec<usize>>>,
+    incoming: Rc<Incoming>,
+    schedule: Vec<usize>,
+}
+
+/// A handle to a [`SimPool`](SimPool) that implements
+/// [`Spawn`](futures_core::task::Spawn) and
+/// [`LocalSpawn`](futures_core::task::LocalSpawn).
+#[derive(Clone, Debug)]
+pub struct SimSpawner {
+    incoming: Weak<Incoming>,
+}
+
+type Incoming = RefCell<Vec<LocalFutureObj<'static, ()>>>;
+
+#[derive(Debug)]
+struct SimTask {
+    future: LocalFutureObj<'static, ()>,
+    waker: Arc<SimWaker>,
+}
+
+#[derive(Debug)]
+struct SimWaker {
+    id: usize,
+    // Whether the task is in the ready list, to avoid queueing it twice
+    queued: AtomicBool,
+    ready: Arc<Mutex<Vec<usize>>>,
+}
+
+impl ArcWake for SimWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if !arc_self.queued.swap(true, Ordering::SeqCst) {
+            arc_self.ready.lock().unwrap().push(arc_self.id);
+        }
+    }
+}
+
+impl SimPool {
+    /// Create a new, empty pool whose scheduling decisions are derived from
+    /// `seed`.
+    pub fn new(seed: u64) -> SimPool {
+        SimPool {
+            seed,
+            rng: Rng::new(seed),
+            tasks: Vec::new(),
+            num_tasks: 0,
+            ready: Arc::new(Mutex::new(Vec::new())),
+            incoming: Default::default(),
+            schedule: Vec::new(),
+        }
+    }
+
+    /// Get a clonable handle to the pool as a [`Spawn`](futures_core::task::Spawn).
+    pub fn spawner(&self) -> SimSpawner {
+        SimSpawner {
+            incoming: Rc::downgrade(&self.incoming),
+        }
+    }
+
+    /// The seed this pool was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The ids of the tasks polled so far, in the order they were polled.
+    pub fn schedule(&self) -> &[usize] {
+        &self.schedule
+    }
+
+    /// Returns the number of tasks spawned onto the pool which have not
+    /// completed yet.
+    pub fn num_tasks(&self) -> usize {
+        self.num_tasks + self.incoming.borrow().len()
+    }
+
+    /// Polls a single ready task, chosen at random.
+    ///
+    /// Returns `false` if no task was ready to be polled.
+    pub fn run_one(&mut self) -> bool {
+        let _enter = enter()
+            .expect("cannot execute `SimPool` executor from within \
+                     another executor");
+        self.poll_one()
+    }
+
+    /// Polls ready tasks, one at a time and in random order, until every
+    /// remaining task is waiting to be woken.
+    ///
+    /// Use [`num_tasks`](SimPool::num_tasks) afterwards to tell whether all
+    /// tasks completed or some of them are stuck.
+    pub fn run_until_stalled(&mut self) {
+        let _enter = enter()
+            .expect("cannot execute `SimPool` executor from within \
+                     another executor");
+        while self.poll_one() {}
+    }
+
+    /// Runs the tasks in the pool, along with `future`, until `future`
+    /// completes or no task can make progress anymore.
+    ///
+    /// `future` is scheduled like any other task. Returns `None` if the pool
+    /// stalled before `future` completed.
+    pub fn run_until<F: Future + 'static>(&mut self, future: F) -> Option<F::Output> {
+        let output = Rc::new(RefCell::new(None));
+        let output2 = output.clone();
+        self.spawner()
+            .spawn_local_obj(LocalFutureObj::new(Box::new(future.map(move |out| {
+                *output2.borrow_mut() = Some(out);
+            }))))
+            .unwrap();
+
+        let _enter = enter()
+            .expect("cannot execute `SimPool` executor from within \
+                     another executor");
+        while output.borrow().is_none() && self.poll_one() {}
+        let res = output.borrow_mut().take();
+        res
+    }
+
+    // Assign ids to newly-spawned tasks and mark them as ready.
+    fn drain_incoming(&mut self) {
+        let incoming = self.incoming.borrow_mut().drain(..).collect::<Vec<_>>();
+        for future in incoming {
+            let waker = Arc::new(SimWaker {
+                id: self.tasks.len(),
+                queued: AtomicBool::new(false),
+                ready: self.ready.clone(),
+            });
+            ArcWake::wake_by_ref(&waker);
+            self.tasks.push(Some(SimTask { future, waker }));
+            self.num_tasks += 1;
+        }
+    }
+
+    fn poll_one(&mut self) -> bool {
+        self.drain_incoming();
+        let id = {
+            let mut ready = self.ready.lock().unwrap();
+            if ready.is_empty() {
+                return false;
+            }
+            let idx = self.rng.below(ready.len());
+            ready.swap_remove(idx)
+        };
+
+        let task = match self.tasks[id] {
+            Some(ref mut task) => task,
+            // woken after completing
+            None => return true,
+        };
+        self.schedule.push(id);
+
+        // Unset the queued flag before polling, so that a wakeup during the
+        // call to `poll` queues the task again.
+        task.waker.queued.store(false, Ordering::SeqCst);
+        let waker = waker_ref(&task.waker);
+        let mut cx = Context::from_waker(&waker);
+        if let Poll::Ready(()) = task.future.poll_unpin(&mut cx) {
+            self.tasks[id] = None;
+            self.num_tasks -= 1;
+        }
+        true
+    }
+}
+
+impl Spawn for SimSpawner {
+    fn spawn_obj(
+        &mut self,
+        future: FutureObj<'static, ()>,
+    ) -> Result<(), SpawnError> {
+        self.spawn_local_obj(future.into())
+    }
+
+    fn status(&self) -> Result<(), SpawnError> {
+        self.status_local()
+    }
+}
+
+impl LocalSpawn for SimSpawner {
+    fn spawn_local_obj(
+        &mut self,
+        future: LocalFutureObj<'static, ()>,
+    ) -> Result<(), SpawnError> {
+        if let Some(incoming) = self.incoming.upgrade() {
+            incoming.borrow_mut().push(future);
+            Ok(())
+        } else {
+            Err(SpawnError::shutdown())
+        }
+    }
+
+    fn status_local(&self) -> Result<(), SpawnError> {
+        if self.incoming.upgrade().is_some() {
+            Ok(())
+        } else {
+            Err(SpawnError::shutdown())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_core::task::{Context, LocalSpawn, Poll};
+    use futures_util::future::poll_fn;
+    use std::collections::HashSet;
+
+    use super::SimPool;
+
+    // Spawns tasks which each yield a few times before completing, and
+    // returns the resulting schedule.
+    fn run_with_seed(seed: u64) -> Vec<usize> {
+        let mut pool = SimPool::new(seed);
+        let mut spawner = pool.spawner();
+        for _ in 0..5 {
+            let mut remaining = 3;
+            let future = poll_fn(move |cx: &mut Context<'_>| {
+                if remaining == 0 {
+                    Poll::Ready(())
+                } else {
+                    remaining -= 1;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            });
+            spawner.spawn_local_obj(Box::pin(future).into()).unwrap();
+        }
+        pool.run_until_stalled();
+        assert_eq!(pool.num_tasks(), 0);
+        pool.schedule().to_vec()
+    }
+
+    #[test]
+    fn same_seed_same_schedule() {
+        for seed in 0..10 {
+            assert_eq!(run_with_seed(seed), run_with_seed(seed));
+        }
+    }
+
+    #[test]
+    fn seeds_explore_different_schedules() {
+        let schedules = (0..10).map(run_with_seed).collect::<HashSet<_>>();
+        assert!(schedules.len() > 1);
+    }
+}
@@ -0,0 +1,179 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use slab::Slab;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard};
+
+use super::waiter::Waiter;
+
+/// A barrier which lets a number of tasks wait for each other.
+///
+/// Each task calls [`wait`](Barrier::wait) and awaits the returned future,
+/// which resolves once `n` tasks are waiting. The barrier can then be used
+/// again for the next `n` tasks.
+///
+/// # Examples
+///
+/// ```
+/// use futures::executor::block_on;
+/// use futures::future::join;
+/// use futures::lock::Barrier;
+///
+/// let barrier = Barrier::new(2);
+/// let (a, b) = block_on(join(barrier.wait(), barrier.wait()));
+/// // Exactly one of the tasks is elected leader
+/// assert!(a.is_leader() != b.is_leader());
+/// ```
+pub struct Barrier {
+    state: StdMutex<State>,
+    n: usize,
+}
+
+struct State {
+    // Number of tasks waiting in the current generation
+    count: usize,
+    // Bumped every time the barrier releases its waiting tasks
+    generation: usize,
+    waiters: Slab<Waiter>,
+}
+
+/// The result of waiting on a [`Barrier`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    is_leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Returns whether this task is the leader of the tasks released
+    /// together.
+    ///
+    /// Out of each group of `n` tasks released by the barrier, exactly one is
+    /// the leader: the last one to arrive.
+    pub fn is_leader(&self) -> bool {
+        self.is_leader
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock_state();
+        f.debug_struct("Barrier")
+            .field("n", &self.n)
+            .field("waiting", &state.count)
+            .finish()
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier which releases tasks in groups of `n`.
+    ///
+    /// A barrier created with `n == 0` behaves like one with `n == 1`: every
+    /// task is released right away, as the leader.
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            state: StdMutex::new(State {
+                count: 0,
+                generation: 0,
+                waiters: Slab::new(),
+            }),
+            n,
+        }
+    }
+
+    fn lock_state(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Wait for `n` tasks to reach the barrier.
+    ///
+    /// This method returns a future that will resolve once `n` tasks are
+    /// waiting on the barrier. A task only counts as waiting from the first
+    /// time its future is polled, and stops counting if the future is
+    /// dropped before it resolves.
+    pub fn wait(&self) -> BarrierWaitFuture<'_> {
+        BarrierWaitFuture {
+            barrier: Some(self),
+            generation: 0,
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+}
+
+// Sentinel for when no slot in the `Slab` has been dedicated to this object.
+const WAIT_KEY_NONE: usize = usize::MAX;
+
+/// A future which resolves when enough tasks are waiting on a [`Barrier`].
+pub struct BarrierWaitFuture<'a> {
+    // `None` indicates that the barrier released this task.
+    barrier: Option<&'a Barrier>,
+    // The generation this task is waiting in, once it has arrived
+    generation: usize,
+    wait_key: usize,
+}
+
+impl fmt::Debug for BarrierWaitFuture<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BarrierWaitFuture")
+            .field("was_released", &self.barrier.is_none())
+            .field("barrier", &self.barrier)
+            .finish()
+    }
+}
+
+impl FusedFuture for BarrierWaitFuture<'_> {
+    fn is_terminated(&self) -> bool {
+        self.barrier.is_none()
+    }
+}
+
+impl Future for BarrierWaitFuture<'_> {
+    type Output = BarrierWaitResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let barrier = self.barrier.expect("polled BarrierWaitFuture after completion");
+        let mut state = barrier.lock_state();
+
+        if self.wait_key != WAIT_KEY_NONE {
+            if state.generation != self.generation {
+                // Released, and our slot was cleared along with the others
+                self.barrier = None;
+                return Poll::Ready(BarrierWaitResult { is_leader: false });
+            }
+            state.waiters[self.wait_key].register(cx.waker());
+            return Poll::Pending;
+        }
+
+        state.count += 1;
+        if state.count >= barrier.n {
+            state.count = 0;
+            state.generation = state.generation.wrapping_add(1);
+            for (_i, waiter) in state.waiters.iter_mut() {
+                waiter.wake();
+            }
+            state.waiters.clear();
+            self.barrier = None;
+            return Poll::Ready(BarrierWaitResult { is_leader: true });
+        }
+
+        self.generation = state.generation;
+        self.wait_key = state.waiters.insert(Waiter::Waiting(cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Drop for BarrierWaitFuture<'_> {
+    fn drop(&mut self) {
+        if let Some(barrier) = self.barrier {
+            if self.wait_key != WAIT_KEY_NONE {
+                // This task arrived, but was dropped before the barrier
+                // released it. It no longer counts as waiting.
+                let mut state = barrier.lock_state();
+                if state.generation == self.generation {
+                    state.waiters.remove(self.wait_key);
+                    state.count -= 1;
+                }
+            }
+        }
+    }
+}
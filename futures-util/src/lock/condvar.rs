@@ -0,0 +1,110 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use std::fmt;
+use std::pin::Pin;
+
+use super::mutex::{Mutex, MutexGuard, MutexLockFuture};
+use super::notify::{Notified, Notify};
+
+/// A futures-aware condition variable, for use with [`Mutex`].
+///
+/// A task holding the lock of a [`Mutex`] can wait on a condition variable,
+/// which unlocks the mutex until another task notifies the condition
+/// variable, and then locks it again. Like with the standard library's
+/// condition variable, notifications are not stored for tasks which start
+/// waiting later, so the condition should be checked before waiting, in a
+/// loop.
+pub struct Condvar {
+    notify: Notify,
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Condvar").finish()
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Condvar {
+        Condvar::new()
+    }
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub fn new() -> Condvar {
+        Condvar { notify: Notify::new() }
+    }
+
+    /// Unlocks the mutex held by `guard` and waits for a notification.
+    ///
+    /// This method returns a future that will resolve once the condition
+    /// variable has been notified and the mutex has been locked again.
+    /// Notifications sent after this method returns are not missed, even if
+    /// the future hasn't been polled yet.
+    pub fn wait<'a, T>(&'a self, guard: MutexGuard<'a, T>) -> CondvarWaitFuture<'a, T> {
+        let mut notified = self.notify.notified();
+        notified.enable();
+        let mutex = MutexGuard::mutex(&guard);
+        drop(guard);
+        CondvarWaitFuture {
+            notified,
+            mutex,
+            lock: None,
+        }
+    }
+
+    /// Wakes up one task waiting on this condition variable.
+    ///
+    /// If no task is waiting, this does nothing.
+    pub fn notify_one(&self) {
+        self.notify.notify_waiter();
+    }
+
+    /// Wakes up all the tasks waiting on this condition variable.
+    pub fn notify_all(&self) {
+        self.notify.notify_waiters();
+    }
+}
+
+/// A future which resolves when the condition variable has been notified and
+/// the mutex has been locked again.
+pub struct CondvarWaitFuture<'a, T> {
+    notified: Notified<'a>,
+    mutex: &'a Mutex<T>,
+    // Set once notified, to lock the mutex again
+    lock: Option<MutexLockFuture<'a, T>>,
+}
+
+impl<T> fmt::Debug for CondvarWaitFuture<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CondvarWaitFuture")
+            .field("was_notified", &self.notified.is_terminated())
+            .field("mutex", &self.mutex)
+            .finish()
+    }
+}
+
+impl<T> FusedFuture for CondvarWaitFuture<'_, T> {
+    fn is_terminated(&self) -> bool {
+        match &self.lock {
+            Some(lock) => lock.is_terminated(),
+            None => false,
+        }
+    }
+}
+
+impl<'a, T> Future for CondvarWaitFuture<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.lock.is_none() {
+            match Pin::new(&mut this.notified).poll(cx) {
+                Poll::Ready(()) => this.lock = Some(this.mutex.lock()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Pin::new(this.lock.as_mut().unwrap()).poll(cx)
+    }
+}
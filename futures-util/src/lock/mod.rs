@@ -3,7 +3,47 @@
 #[cfg(feature = "std")]
 mod mutex;
 #[cfg(feature = "std")]
-pub use self::mutex::{Mutex, MutexLockFuture, MutexGuard};
+pub use self::mutex::{
+    MappedMutexGuard, Mutex, MutexLockFuture, MutexGuard, OwnedMutexGuard, OwnedMutexLockFuture,
+};
+
+#[cfg(feature = "std")]
+mod barrier;
+#[cfg(feature = "std")]
+pub use self::barrier::{Barrier, BarrierWaitFuture, BarrierWaitResult};
+
+#[cfg(feature = "std")]
+mod condvar;
+#[cfg(feature = "std")]
+pub use self::condvar::{Condvar, CondvarWaitFuture};
+
+#[cfg(feature = "std")]
+mod notify;
+#[cfg(feature = "std")]
+pub use self::notify::{Notified, Notify};
+
+#[cfg(feature = "std")]
+mod once_cell;
+#[cfg(feature = "std")]
+pub use self::once_cell::{OnceCell, OnceCellGetOrInitFuture, OnceCellGetOrTryInitFuture};
+
+#[cfg(feature = "std")]
+mod rwlock;
+#[cfg(feature = "std")]
+pub use self::rwlock::{
+    RwLock, RwLockReadFuture, RwLockReadGuard, RwLockWriteFuture, RwLockWriteGuard,
+};
+
+#[cfg(feature = "std")]
+mod semaphore;
+#[cfg(feature = "std")]
+pub use self::semaphore::{
+    AcquireError, OwnedSemaphorePermit, Semaphore, SemaphoreAcquireFuture,
+    SemaphoreAcquireOwnedFuture, SemaphorePermit, TryAcquireError,
+};
+
+#[cfg(feature = "std")]
+mod waiter;
 
 mod bilock;
 #[cfg(any(test, feature = "bench"))]
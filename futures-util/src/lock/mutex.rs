@@ -3,24 +3,43 @@ use futures_core::task::{Context, Poll, Waker};
 use slab::Slab;
 use std::{fmt, mem, usize};
 use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
-use std::sync::Mutex as StdMutex;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use super::waiter::Waiter;
+
 /// A futures-aware mutex.
+///
+/// By default, a task which finds the mutex unlocked acquires it right away,
+/// even if other tasks have been waiting for it. This gives the best
+/// throughput, but under heavy contention a waiting task can be overtaken
+/// again and again. A mutex created with [`new_fair`](Mutex::new_fair)
+/// instead hands the lock over to the task which has been waiting the
+/// longest whenever it is unlocked, so every task eventually gets its turn.
 pub struct Mutex<T> {
     state: AtomicUsize,
+    fair: bool,
     value: UnsafeCell<T>,
-    waiters: StdMutex<Slab<Waiter>>,
+    waiters: StdMutex<Waiters>,
 }
 
 impl<T> fmt::Debug for Mutex<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let state = self.state.load(Ordering::SeqCst);
+        // A fair mutex tracks its waiters in the slab only
+        let has_waiters = if self.fair {
+            !self.waiters.lock().unwrap().slab.is_empty()
+        } else {
+            (state & HAS_WAITERS) != 0
+        };
         f.debug_struct("Mutex")
             .field("is_locked", &((state & IS_LOCKED) != 0))
-            .field("has_waiters", &((state & HAS_WAITERS) != 0))
+            .field("is_fair", &self.fair)
+            .field("has_waiters", &has_waiters)
             .finish()
     }
 }
@@ -31,23 +50,48 @@ impl<T: Default> Default for Mutex<T> {
     }
 }
 
-enum Waiter {
-    Waiting(Waker),
-    Woken,
+struct Waiters {
+    slab: Slab<Waiter>,
+    // Keys of the waiters in `slab` which haven't been woken yet, oldest
+    // first
+    queue: VecDeque<usize>,
 }
 
-impl Waiter {
-    fn register(&mut self, waker: &Waker) {
-        match self {
-            Waiter::Waiting(waker) if waker.will_wake(waker) => {},
-            _ => *self = Waiter::Waiting(waker.clone()),
+impl Waiters {
+    fn insert(&mut self, waker: &Waker) -> usize {
+        let key = self.slab.insert(Waiter::Waiting(waker.clone()));
+        self.queue.push_back(key);
+        key
+    }
+
+    fn register(&mut self, key: usize, waker: &Waker) {
+        if let Waiter::Woken = self.slab[key] {
+            // Woken, but didn't get the lock: back to the front of the line
+            self.queue.push_front(key);
+        }
+        self.slab[key].register(waker);
+    }
+
+    // Removes a waiter, returning whether it had been woken.
+    fn remove(&mut self, key: usize) -> bool {
+        match self.slab.remove(key) {
+            Waiter::Waiting(_) => {
+                self.queue.retain(|&k| k != key);
+                false
+            }
+            Waiter::Woken => true,
         }
     }
 
-    fn wake(&mut self) {
-        match mem::replace(self, Waiter::Woken) {
-            Waiter::Waiting(waker) => waker.wake(),
-            Waiter::Woken => {},
+    // Wakes the oldest waiter which hasn't been woken yet, returning whether
+    // there was one.
+    fn wake_next(&mut self) -> bool {
+        match self.queue.pop_front() {
+            Some(key) => {
+                self.slab[key].wake();
+                true
+            }
+            None => false,
         }
     }
 }
@@ -59,25 +103,68 @@ const HAS_WAITERS: usize = 1 << 1;
 impl<T> Mutex<T> {
     /// Creates a new futures-aware mutex.
     pub fn new(t: T) -> Mutex<T> {
+        Mutex::with_fairness(t, false)
+    }
+
+    /// Creates a new futures-aware mutex which hands the lock over to its
+    /// waiters in the order they started waiting.
+    ///
+    /// When a fair mutex is unlocked while tasks are waiting for it, it stays
+    /// locked on behalf of the task which has been waiting the longest, so
+    /// neither [`try_lock`](Mutex::try_lock) nor newly started
+    /// [`lock`](Mutex::lock) futures can overtake that task.
+    pub fn new_fair(t: T) -> Mutex<T> {
+        Mutex::with_fairness(t, true)
+    }
+
+    fn with_fairness(t: T, fair: bool) -> Mutex<T> {
         Mutex {
             state: AtomicUsize::new(0),
+            fair,
             value: UnsafeCell::new(t),
-            waiters: StdMutex::new(Slab::new()),
+            waiters: StdMutex::new(Waiters {
+                slab: Slab::new(),
+                queue: VecDeque::new(),
+            }),
         }
     }
 
+    /// Consumes this mutex, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the mutex mutably, no actual locking needs to
+    /// take place.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value.get() }
+    }
+
     /// Attempt to acquire the lock immediately.
     ///
     /// If the lock is currently held, this will return `None`.
     pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
-        let old_state = self.state.fetch_or(IS_LOCKED, Ordering::Acquire);
-        if (old_state & IS_LOCKED) == 0 {
+        if self.try_acquire() {
             Some(MutexGuard { mutex: self })
         } else {
             None
         }
     }
 
+    /// Attempt to acquire the lock immediately, returning a guard which
+    /// keeps the mutex alive.
+    ///
+    /// If the lock is currently held, this will return `None`.
+    pub fn try_lock_owned(self: &Arc<Self>) -> Option<OwnedMutexGuard<T>> {
+        if self.try_acquire() {
+            Some(OwnedMutexGuard { mutex: self.clone() })
+        } else {
+            None
+        }
+    }
+
     /// Acquire the lock asynchronously.
     ///
     /// This method returns a future that will resolve once the lock has been
@@ -89,23 +176,117 @@ impl<T> Mutex<T> {
         }
     }
 
+    /// Acquire the lock asynchronously, resolving to a guard which keeps the
+    /// mutex alive.
+    ///
+    /// This behaves like [`lock`](Mutex::lock), but neither the future nor
+    /// the guard is tied to the lifetime of a borrow, so the guard can be
+    /// held across a spawned task boundary.
+    pub fn lock_owned(self: &Arc<Self>) -> OwnedMutexLockFuture<T> {
+        OwnedMutexLockFuture {
+            mutex: Some(self.clone()),
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+
+    fn try_acquire(&self) -> bool {
+        let old_state = self.state.fetch_or(IS_LOCKED, Ordering::Acquire);
+        (old_state & IS_LOCKED) == 0
+    }
+
+    fn poll_lock(&self, wait_key: &mut usize, cx: &mut Context<'_>) -> Poll<()> {
+        if self.fair {
+            return self.poll_lock_fair(wait_key, cx);
+        }
+
+        if self.try_acquire() {
+            self.remove_waker(*wait_key, false);
+            *wait_key = WAIT_KEY_NONE;
+            return Poll::Ready(());
+        }
+
+        {
+            let mut waiters = self.waiters.lock().unwrap();
+            if *wait_key == WAIT_KEY_NONE {
+                *wait_key = waiters.insert(cx.waker());
+                if waiters.slab.len() == 1 {
+                    self.state.fetch_or(HAS_WAITERS, Ordering::Relaxed); // released by mutex unlock
+                }
+            } else {
+                waiters.register(*wait_key, cx.waker());
+            }
+        }
+
+        // Ensure that we haven't raced `MutexGuard::drop`'s unlock path by
+        // attempting to acquire the lock again.
+        if self.try_acquire() {
+            self.remove_waker(*wait_key, false);
+            *wait_key = WAIT_KEY_NONE;
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }
+
+    // A fair mutex is only ever unlocked while holding the waiters lock and
+    // with no waiters left, so holding the waiters lock here is enough to
+    // avoid racing the unlock path.
+    fn poll_lock_fair(&self, wait_key: &mut usize, cx: &mut Context<'_>) -> Poll<()> {
+        let mut waiters = self.waiters.lock().unwrap();
+        if *wait_key != WAIT_KEY_NONE {
+            if let Waiter::Woken = waiters.slab[*wait_key] {
+                // The previous holder handed the lock over to us.
+                waiters.slab.remove(*wait_key);
+                *wait_key = WAIT_KEY_NONE;
+                return Poll::Ready(());
+            }
+            waiters.register(*wait_key, cx.waker());
+            return Poll::Pending;
+        }
+
+        if self.try_acquire() {
+            return Poll::Ready(());
+        }
+        *wait_key = waiters.insert(cx.waker());
+        Poll::Pending
+    }
+
+    fn unlock(&self) {
+        if self.fair {
+            let mut waiters = self.waiters.lock().unwrap();
+            self.hand_off(&mut waiters);
+        } else {
+            let old_state = self.state.fetch_and(!IS_LOCKED, Ordering::AcqRel);
+            if (old_state & HAS_WAITERS) != 0 {
+                self.waiters.lock().unwrap().wake_next();
+            }
+        }
+    }
+
+    // Passes the lock of a fair mutex to its oldest waiter, or unlocks it if
+    // there are none.
+    fn hand_off(&self, waiters: &mut Waiters) {
+        if !waiters.wake_next() {
+            self.state.fetch_and(!IS_LOCKED, Ordering::Release);
+        }
+    }
+
     fn remove_waker(&self, wait_key: usize, wake_another: bool) {
         if wait_key != WAIT_KEY_NONE {
             let mut waiters = self.waiters.lock().unwrap();
-            match waiters.remove(wait_key) {
-                Waiter::Waiting(_) => {},
-                Waiter::Woken => {
+            if waiters.remove(wait_key) && wake_another {
+                if self.fair {
+                    // We were handed the lock, but then dropped before we
+                    // could take it. Pass it on.
+                    self.hand_off(&mut waiters);
+                } else {
                     // We were awoken, but then dropped before we could
                     // wake up to acquire the lock. Wake up another
                     // waiter.
-                    if wake_another {
-                        if let Some((_i, waiter)) = waiters.iter_mut().next() {
-                            waiter.wake();
-                        }
-                    }
+                    waiters.wake_next();
                 }
             }
-            if waiters.is_empty() {
+            if waiters.slab.is_empty() {
                 self.state.fetch_and(!HAS_WAITERS, Ordering::Relaxed); // released by mutex unlock
             }
         }
@@ -149,34 +330,13 @@ impl<'a, T> Future for MutexLockFuture<'a, T> {
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let mutex = self.mutex.expect("polled MutexLockFuture after completion");
-
-        if let Some(lock) = mutex.try_lock() {
-            mutex.remove_waker(self.wait_key, false);
-            self.mutex = None;
-            return Poll::Ready(lock);
-        }
-
-        {
-            let mut waiters = mutex.waiters.lock().unwrap();
-            if self.wait_key == WAIT_KEY_NONE {
-                self.wait_key = waiters.insert(Waiter::Waiting(cx.waker().clone()));
-                if waiters.len() == 1 {
-                    mutex.state.fetch_or(HAS_WAITERS, Ordering::Relaxed); // released by mutex unlock
-                }
-            } else {
-                waiters[self.wait_key].register(cx.waker());
+        match mutex.poll_lock(&mut self.wait_key, cx) {
+            Poll::Ready(()) => {
+                self.mutex = None;
+                Poll::Ready(MutexGuard { mutex })
             }
+            Poll::Pending => Poll::Pending,
         }
-
-        // Ensure that we haven't raced `MutexGuard::drop`'s unlock path by
-        // attempting to acquire the lock again.
-        if let Some(lock) = mutex.try_lock() {
-            mutex.remove_waker(self.wait_key, false);
-            self.mutex = None;
-            return Poll::Ready(lock);
-        }
-
-        Poll::Pending
     }
 }
 
@@ -192,6 +352,58 @@ impl<T> Drop for MutexLockFuture<'_, T> {
     }
 }
 
+/// A future which resolves when the target mutex has been successfully
+/// acquired, holding on to the mutex through an `Arc`.
+pub struct OwnedMutexLockFuture<T> {
+    // `None` indicates that the mutex was successfully acquired.
+    mutex: Option<Arc<Mutex<T>>>,
+    wait_key: usize,
+}
+
+impl<T> fmt::Debug for OwnedMutexLockFuture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedMutexLockFuture")
+            .field("was_acquired", &self.mutex.is_none())
+            .field("mutex", &self.mutex)
+            .field("wait_key", &(
+                    if self.wait_key == WAIT_KEY_NONE {
+                        None
+                    } else {
+                        Some(self.wait_key)
+                    }
+                ))
+            .finish()
+    }
+}
+
+impl<T> FusedFuture for OwnedMutexLockFuture<T> {
+    fn is_terminated(&self) -> bool {
+        self.mutex.is_none()
+    }
+}
+
+impl<T> Future for OwnedMutexLockFuture<T> {
+    type Output = OwnedMutexGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mutex = this.mutex.as_ref().expect("polled OwnedMutexLockFuture after completion");
+        match mutex.poll_lock(&mut this.wait_key, cx) {
+            Poll::Ready(()) => Poll::Ready(OwnedMutexGuard { mutex: this.mutex.take().unwrap() }),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for OwnedMutexLockFuture<T> {
+    fn drop(&mut self) {
+        if let Some(mutex) = &self.mutex {
+            // This future was dropped before it acquired the mutex.
+            mutex.remove_waker(self.wait_key, true);
+        }
+    }
+}
+
 /// An RAII guard returned by the `lock` and `try_lock` methods.
 /// When this structure is dropped (falls out of scope), the lock will be
 /// unlocked.
@@ -208,15 +420,43 @@ impl<T: fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
     }
 }
 
+impl<'a, T> MutexGuard<'a, T> {
+    /// Returns a guard for a component of the locked data, such as a field
+    /// of a struct.
+    ///
+    /// The mutex stays locked until the returned guard is dropped. This is an
+    /// associated function that needs to be used as `MutexGuard::map(...)`,
+    /// so that it doesn't conflict with methods on the locked data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::lock::{Mutex, MutexGuard};
+    ///
+    /// let mutex = Mutex::new((1, String::from("a")));
+    /// let mut name = MutexGuard::map(mutex.try_lock().unwrap(), |pair| &mut pair.1);
+    /// name.push('b');
+    /// drop(name);
+    /// assert_eq!(mutex.try_lock().unwrap().1, "ab");
+    /// ```
+    pub fn map<U: ?Sized, F>(this: Self, f: F) -> MappedMutexGuard<'a, T, U>
+        where F: FnOnce(&mut T) -> &mut U,
+    {
+        let mutex = this.mutex;
+        let value = f(unsafe { &mut *mutex.value.get() }) as *mut U;
+        // The mapped guard takes over unlocking the mutex
+        mem::forget(this);
+        MappedMutexGuard { mutex, value, _marker: PhantomData }
+    }
+
+    pub(super) fn mutex(this: &Self) -> &'a Mutex<T> {
+        this.mutex
+    }
+}
+
 impl<T> Drop for MutexGuard<'_, T> {
     fn drop(&mut self) {
-        let old_state = self.mutex.state.fetch_and(!IS_LOCKED, Ordering::AcqRel);
-        if (old_state & HAS_WAITERS) != 0 {
-            let mut waiters = self.mutex.waiters.lock().unwrap();
-            if let Some((_i, waiter)) = waiters.iter_mut().next() {
-                waiter.wake();
-            }
-        }
+        self.mutex.unlock();
     }
 }
 
@@ -233,6 +473,101 @@ impl<T> DerefMut for MutexGuard<'_, T> {
     }
 }
 
+/// An RAII guard returned by the `lock_owned` and `try_lock_owned` methods.
+/// When this structure is dropped (falls out of scope), the lock will be
+/// unlocked.
+pub struct OwnedMutexGuard<T> {
+    mutex: Arc<Mutex<T>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for OwnedMutexGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedMutexGuard")
+            .field("value", &**self)
+            .field("mutex", &self.mutex)
+            .finish()
+    }
+}
+
+impl<T> OwnedMutexGuard<T> {
+    /// Returns the mutex this guard has locked.
+    pub fn mutex(this: &Self) -> &Arc<Mutex<T>> {
+        &this.mutex
+    }
+}
+
+impl<T> Drop for OwnedMutexGuard<T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T> Deref for OwnedMutexGuard<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for OwnedMutexGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+/// An RAII guard for a component of the data locked by a mutex, returned by
+/// [`MutexGuard::map`]. When this structure is dropped (falls out of scope),
+/// the lock will be unlocked.
+pub struct MappedMutexGuard<'a, T, U: ?Sized> {
+    mutex: &'a Mutex<T>,
+    value: *mut U,
+    _marker: PhantomData<&'a mut U>,
+}
+
+impl<T, U: ?Sized + fmt::Debug> fmt::Debug for MappedMutexGuard<'_, T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedMutexGuard")
+            .field("value", &&**self)
+            .field("mutex", &self.mutex)
+            .finish()
+    }
+}
+
+impl<'a, T, U: ?Sized> MappedMutexGuard<'a, T, U> {
+    /// Returns a guard for a component of the data this guard gives access
+    /// to.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `MappedMutexGuard::map(...)`, like [`MutexGuard::map`].
+    pub fn map<V: ?Sized, F>(this: Self, f: F) -> MappedMutexGuard<'a, T, V>
+        where F: FnOnce(&mut U) -> &mut V,
+    {
+        let mutex = this.mutex;
+        let value = f(unsafe { &mut *this.value }) as *mut V;
+        mem::forget(this);
+        MappedMutexGuard { mutex, value, _marker: PhantomData }
+    }
+}
+
+impl<T, U: ?Sized> Drop for MappedMutexGuard<'_, T, U> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T, U: ?Sized> Deref for MappedMutexGuard<'_, T, U> {
+    type Target = U;
+    fn deref(&self) -> &U {
+        unsafe { &*self.value }
+    }
+}
+
+impl<T, U: ?Sized> DerefMut for MappedMutexGuard<'_, T, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        unsafe { &mut *self.value }
+    }
+}
+
 // Mutexes can be moved freely between threads and acquired on any thread so long
 // as the inner value can be safely sent between threads.
 unsafe impl<T: Send> Send for Mutex<T> {}
@@ -241,10 +576,16 @@ unsafe impl<T: Send> Sync for Mutex<T> {}
 // It's safe to switch which thread the acquire is being attempted on so long as
 // `T` can be accessed on that thread.
 unsafe impl<T: Send> Send for MutexLockFuture<'_, T> {}
+unsafe impl<T: Send> Send for OwnedMutexLockFuture<T> {}
 // doesn't have any interesting `&self` methods (only Debug)
 unsafe impl<T> Sync for MutexLockFuture<'_, T> {}
+unsafe impl<T> Sync for OwnedMutexLockFuture<T> {}
 
 // Safe to send since we don't track any thread-specific details-- the inner
 // lock is essentially spinlock-equivalent (attempt to flip an atomic bool)
 unsafe impl<T: Send> Send for MutexGuard<'_, T> {}
 unsafe impl<T: Sync> Sync for MutexGuard<'_, T> {}
+unsafe impl<T: Send> Send for OwnedMutexGuard<T> {}
+unsafe impl<T: Sync> Sync for OwnedMutexGuard<T> {}
+unsafe impl<T: Send, U: ?Sized + Send> Send for MappedMutexGuard<'_, T, U> {}
+unsafe impl<T: Sync, U: ?Sized + Sync> Sync for MappedMutexGuard<'_, T, U> {}
@@ -0,0 +1,261 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll, Waker};
+use slab::Slab;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard};
+
+/// Notifies tasks waiting for an event, without sending any data.
+///
+/// Tasks wait for a notification by awaiting the future returned by
+/// [`notified`](Notify::notified). [`notify_one`](Notify::notify_one) wakes
+/// the task which has been waiting the longest; if no task is waiting, the
+/// notification is stored and the next task to wait gets it right away.
+/// [`notify_waiters`](Notify::notify_waiters) wakes all the tasks waiting at
+/// the time of the call, and isn't stored.
+///
+/// # Examples
+///
+/// ```
+/// use futures::executor::block_on;
+/// use futures::lock::Notify;
+///
+/// let notify = Notify::new();
+/// notify.notify_one();
+/// // The notification was stored, so this doesn't wait
+/// block_on(notify.notified());
+/// ```
+pub struct Notify {
+    state: StdMutex<State>,
+}
+
+struct State {
+    // Set by `notify_one` when there was no task waiting
+    permit: bool,
+    // Bumped by every call to `notify_waiters`
+    generation: usize,
+    waiters: Slab<Entry>,
+    // Keys of the waiters in `waiters` which haven't been notified yet,
+    // oldest first
+    queue: VecDeque<usize>,
+}
+
+struct Entry {
+    waker: Option<Waker>,
+    notified: Option<Wakeup>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Wakeup {
+    One,
+    // Like `One`, but never stored for a later waiter
+    Waiter,
+    All,
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock_state();
+        f.debug_struct("Notify")
+            .field("has_permit", &state.permit)
+            .field("has_waiters", &!state.queue.is_empty())
+            .finish()
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl State {
+    fn notify_one(&mut self) {
+        if !self.wake_next(Wakeup::One) {
+            self.permit = true;
+        }
+    }
+
+    // Wakes the waiter which has been waiting the longest, returning whether
+    // there was one.
+    fn wake_next(&mut self, wakeup: Wakeup) -> bool {
+        match self.queue.pop_front() {
+            Some(key) => {
+                self.wake(key, wakeup);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn wake(&mut self, key: usize, wakeup: Wakeup) {
+        let entry = &mut self.waiters[key];
+        entry.notified = Some(wakeup);
+        if let Some(waker) = entry.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Notify {
+    /// Creates a new `Notify` with no stored notification.
+    pub fn new() -> Notify {
+        Notify {
+            state: StdMutex::new(State {
+                permit: false,
+                generation: 0,
+                waiters: Slab::new(),
+                queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn lock_state(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Wakes the task which has been waiting the longest, or stores the
+    /// notification for the next task to wait if there is none.
+    ///
+    /// At most one notification is stored: calling this several times while
+    /// no task is waiting only lets one task through.
+    pub fn notify_one(&self) {
+        self.lock_state().notify_one();
+    }
+
+    // Wakes the task which has been waiting the longest, if any, without
+    // storing the notification otherwise.
+    pub(super) fn notify_waiter(&self) {
+        self.lock_state().wake_next(Wakeup::Waiter);
+    }
+
+    /// Wakes all the tasks currently waiting.
+    ///
+    /// This includes the [`Notified`] futures which have been created but
+    /// not yet polled. Unlike [`notify_one`](Notify::notify_one), the
+    /// notification isn't stored for tasks which start waiting later.
+    pub fn notify_waiters(&self) {
+        let mut state = self.lock_state();
+        state.generation = state.generation.wrapping_add(1);
+        while let Some(key) = state.queue.pop_front() {
+            state.wake(key, Wakeup::All);
+        }
+    }
+
+    /// Wait for a notification.
+    ///
+    /// This method returns a future that will resolve once a notification
+    /// has been received.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: Some(self),
+            generation: self.lock_state().generation,
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+}
+
+// Sentinel for when no slot in the `Slab` has been dedicated to this object.
+const WAIT_KEY_NONE: usize = usize::MAX;
+
+/// A future which resolves when a notification has been received from a
+/// [`Notify`].
+pub struct Notified<'a> {
+    // `None` indicates that the notification was received.
+    notify: Option<&'a Notify>,
+    // The value of the `notify_waiters` counter when this was created
+    generation: usize,
+    wait_key: usize,
+}
+
+impl Notified<'_> {
+    // Takes a place in the queue of waiters without waiting yet, so that
+    // notifications sent before this future is first polled aren't missed.
+    pub(super) fn enable(&mut self) {
+        if let Some(notify) = self.notify {
+            if self.wait_key == WAIT_KEY_NONE {
+                let mut state = notify.lock_state();
+                self.wait_key = self.register(&mut state, None);
+            }
+        }
+    }
+
+    fn register(&self, state: &mut State, waker: Option<Waker>) -> usize {
+        let notified = if state.generation != self.generation {
+            Some(Wakeup::All)
+        } else if state.permit {
+            state.permit = false;
+            Some(Wakeup::One)
+        } else {
+            None
+        };
+        let key = state.waiters.insert(Entry { waker, notified });
+        if notified.is_none() {
+            state.queue.push_back(key);
+        }
+        key
+    }
+}
+
+impl fmt::Debug for Notified<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notified")
+            .field("was_notified", &self.notify.is_none())
+            .field("notify", &self.notify)
+            .finish()
+    }
+}
+
+impl FusedFuture for Notified<'_> {
+    fn is_terminated(&self) -> bool {
+        self.notify.is_none()
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let notify = self.notify.expect("polled Notified after completion");
+        let mut state = notify.lock_state();
+
+        if self.wait_key == WAIT_KEY_NONE {
+            self.wait_key = self.register(&mut state, Some(cx.waker().clone()));
+        }
+
+        let entry = &mut state.waiters[self.wait_key];
+        if entry.notified.is_some() {
+            state.waiters.remove(self.wait_key);
+            self.wait_key = WAIT_KEY_NONE;
+            self.notify = None;
+            return Poll::Ready(());
+        }
+        match &entry.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => entry.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if let Some(notify) = self.notify {
+            if self.wait_key != WAIT_KEY_NONE {
+                let mut state = notify.lock_state();
+                let wait_key = self.wait_key;
+                match state.waiters.remove(wait_key).notified {
+                    None => state.queue.retain(|&key| key != wait_key),
+                    // This future was picked by `notify_one`, but then dropped
+                    // before it could be woken. Pass the notification on.
+                    Some(Wakeup::One) => state.notify_one(),
+                    Some(Wakeup::Waiter) => {
+                        state.wake_next(Wakeup::Waiter);
+                    }
+                    Some(Wakeup::All) => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use std::cell::UnsafeCell;
+use std::convert::Infallible;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::semaphore::{Semaphore, SemaphoreAcquireFuture, SemaphorePermit};
+
+/// A cell which can be written to only once, by an asynchronous initializer.
+///
+/// When several tasks call [`get_or_init`](OnceCell::get_or_init) or
+/// [`get_or_try_init`](OnceCell::get_or_try_init) on an empty cell, only one
+/// of them runs its initializer, while the others wait for it to finish. If
+/// the initializer fails, or its future is dropped before it finishes, the
+/// next waiting task runs its own initializer instead.
+///
+/// # Examples
+///
+/// ```
+/// use futures::executor::block_on;
+/// use futures::future::ready;
+/// use futures::lock::OnceCell;
+///
+/// let cell = OnceCell::new();
+/// assert_eq!(cell.get(), None);
+/// assert_eq!(*block_on(cell.get_or_init(|| ready(1))), 1);
+/// // The cell is only initialized once
+/// assert_eq!(*block_on(cell.get_or_init(|| ready(2))), 1);
+/// ```
+pub struct OnceCell<T> {
+    // Set once `value` has been written, after which it is never written
+    // again
+    value_set: AtomicBool,
+    value: UnsafeCell<Option<T>>,
+    // Holds a single permit, owned by whichever task is initializing the
+    // cell. Closed once the cell has been initialized.
+    semaphore: Semaphore,
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCell")
+            .field("value", &self.get())
+            .finish()
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> OnceCell<T> {
+        OnceCell::new()
+    }
+}
+
+impl<T> OnceCell<T> {
+    /// Creates a new, empty cell.
+    pub fn new() -> OnceCell<T> {
+        OnceCell {
+            value_set: AtomicBool::new(false),
+            value: UnsafeCell::new(None),
+            semaphore: Semaphore::new(1),
+        }
+    }
+
+    /// Returns a reference to the value, or `None` if the cell hasn't been
+    /// initialized yet.
+    pub fn get(&self) -> Option<&T> {
+        if self.value_set.load(Ordering::Acquire) {
+            unsafe { (*self.value.get()).as_ref() }
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value, or `None` if the cell
+    /// hasn't been initialized yet.
+    ///
+    /// Since this call borrows the cell mutably, no synchronization needs to
+    /// take place.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { (*self.value.get()).as_mut() }
+    }
+
+    /// Sets the value of the cell.
+    ///
+    /// Returns the value back if the cell has already been initialized, or
+    /// is being initialized by another task.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        // The semaphore is closed once the cell has been initialized
+        match self.semaphore.try_acquire(1) {
+            Ok(_permit) => {
+                self.set_locked(value);
+                Ok(())
+            }
+            Err(_) => Err(value),
+        }
+    }
+
+    /// Consumes the cell, returning the value if it has been initialized.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    /// Returns the value of the cell, initializing it with the future
+    /// returned by `f` if it is empty.
+    ///
+    /// This method returns a future that will resolve once the cell has been
+    /// initialized, either by this call or by another one. `f` is only called
+    /// if no other call is initializing the cell at the time.
+    pub fn get_or_init<F, Fut>(&self, f: F) -> OnceCellGetOrInitFuture<'_, T, F, Fut>
+        where F: FnOnce() -> Fut,
+              Fut: Future<Output = T>,
+    {
+        OnceCellGetOrInitFuture {
+            init: Initializer::new(self, f),
+        }
+    }
+
+    /// Returns the value of the cell, trying to initialize it with the future
+    /// returned by `f` if it is empty.
+    ///
+    /// This behaves like [`get_or_init`](OnceCell::get_or_init), except that
+    /// initialization can fail. If it does, the cell stays empty, the error is
+    /// returned, and the next call waiting to initialize the cell gets to try
+    /// instead.
+    pub fn get_or_try_init<F, Fut, E>(&self, f: F) -> OnceCellGetOrTryInitFuture<'_, T, F, Fut>
+        where F: FnOnce() -> Fut,
+              Fut: Future<Output = Result<T, E>>,
+    {
+        OnceCellGetOrTryInitFuture {
+            init: Initializer::new(self, f),
+        }
+    }
+
+    // Must only be called while holding the semaphore's permit.
+    fn set_locked(&self, value: T) -> &T {
+        unsafe { *self.value.get() = Some(value) };
+        self.value_set.store(true, Ordering::Release);
+        // Let the waiting tasks see the value
+        self.semaphore.close();
+        self.get().unwrap()
+    }
+}
+
+// The value is written by one thread and then read by any number of threads.
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+enum State<'a, F, Fut> {
+    Acquire(SemaphoreAcquireFuture<'a>, Option<F>),
+    Init(SemaphorePermit<'a>, Fut),
+    Done,
+}
+
+// The part shared by the two initializing futures.
+struct Initializer<'a, T, F, Fut> {
+    cell: &'a OnceCell<T>,
+    state: State<'a, F, Fut>,
+}
+
+impl<T, F, Fut> Initializer<'_, T, F, Fut> {
+    fn is_terminated(&self) -> bool {
+        if let State::Done = self.state { true } else { false }
+    }
+}
+
+impl<'a, T, F, Fut> Initializer<'a, T, F, Fut>
+    where F: FnOnce() -> Fut,
+          Fut: Future,
+{
+    fn new(cell: &'a OnceCell<T>, f: F) -> Initializer<'a, T, F, Fut> {
+        Initializer {
+            cell,
+            state: State::Acquire(cell.semaphore.acquire(1), Some(f)),
+        }
+    }
+
+    fn poll<E, G>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        into_result: G,
+    ) -> Poll<Result<&'a T, E>>
+        where G: FnOnce(Fut::Output) -> Result<T, E>,
+    {
+        let mut into_result = Some(into_result);
+
+        // Safe to call `get_unchecked_mut` because we won't move the future.
+        let this = unsafe { Pin::get_unchecked_mut(self) };
+        let cell = this.cell;
+
+        loop {
+            let (permit, f) = match &mut this.state {
+                State::Acquire(acquire, f) => {
+                    if let Some(value) = cell.get() {
+                        this.state = State::Done;
+                        return Poll::Ready(Ok(value));
+                    }
+                    match Pin::new(acquire).poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        // The semaphore is only closed once the cell has been
+                        // initialized.
+                        Poll::Ready(Err(_)) => {
+                            this.state = State::Done;
+                            return Poll::Ready(Ok(cell.get().unwrap()));
+                        }
+                        Poll::Ready(Ok(permit)) => (permit, f.take().unwrap()),
+                    }
+                }
+                State::Init(_permit, fut) => {
+                    let output = match unsafe { Pin::new_unchecked(fut) }.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(output) => output,
+                    };
+                    let res = (into_result.take().unwrap())(output).map(|value| cell.set_locked(value));
+                    // Dropping the permit lets the next waiting task try, if
+                    // initialization failed.
+                    this.state = State::Done;
+                    return Poll::Ready(res);
+                }
+                State::Done => panic!("polled OnceCell future after completion"),
+            };
+
+            this.state = State::Init(permit, f());
+        }
+    }
+}
+
+/// A future which resolves to the value of a [`OnceCell`], returned by
+/// [`get_or_init`](OnceCell::get_or_init).
+pub struct OnceCellGetOrInitFuture<'a, T, F, Fut> {
+    init: Initializer<'a, T, F, Fut>,
+}
+
+/// A future which resolves to the value of a [`OnceCell`], or to the error
+/// its initializer failed with, returned by
+/// [`get_or_try_init`](OnceCell::get_or_try_init).
+pub struct OnceCellGetOrTryInitFuture<'a, T, F, Fut> {
+    init: Initializer<'a, T, F, Fut>,
+}
+
+impl<T, F, Fut: Unpin> Unpin for OnceCellGetOrInitFuture<'_, T, F, Fut> {}
+impl<T, F, Fut: Unpin> Unpin for OnceCellGetOrTryInitFuture<'_, T, F, Fut> {}
+
+impl<T, F, Fut> fmt::Debug for OnceCellGetOrInitFuture<'_, T, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCellGetOrInitFuture")
+            .field("is_terminated", &self.init.is_terminated())
+            .finish()
+    }
+}
+
+impl<T, F, Fut> fmt::Debug for OnceCellGetOrTryInitFuture<'_, T, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCellGetOrTryInitFuture")
+            .field("is_terminated", &self.init.is_terminated())
+            .finish()
+    }
+}
+
+impl<'a, T, F, Fut> FusedFuture for OnceCellGetOrInitFuture<'a, T, F, Fut>
+    where F: FnOnce() -> Fut,
+          Fut: Future<Output = T>,
+{
+    fn is_terminated(&self) -> bool {
+        self.init.is_terminated()
+    }
+}
+
+impl<'a, T, F, Fut, E> FusedFuture for OnceCellGetOrTryInitFuture<'a, T, F, Fut>
+    where F: FnOnce() -> Fut,
+          Fut: Future<Output = Result<T, E>>,
+{
+    fn is_terminated(&self) -> bool {
+        self.init.is_terminated()
+    }
+}
+
+impl<'a, T, F, Fut> Future for OnceCellGetOrInitFuture<'a, T, F, Fut>
+    where F: FnOnce() -> Fut,
+          Fut: Future<Output = T>,
+{
+    type Output = &'a T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'a T> {
+        let init = unsafe { self.map_unchecked_mut(|this| &mut this.init) };
+        match init.poll(cx, Ok::<T, Infallible>) {
+            Poll::Ready(Ok(value)) => Poll::Ready(value),
+            Poll::Ready(Err(never)) => match never {},
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T, F, Fut, E> Future for OnceCellGetOrTryInitFuture<'a, T, F, Fut>
+    where F: FnOnce() -> Fut,
+          Fut: Future<Output = Result<T, E>>,
+{
+    type Output = Result<&'a T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let init = unsafe { self.map_unchecked_mut(|this| &mut this.init) };
+        init.poll(cx, |res| res)
+    }
+}
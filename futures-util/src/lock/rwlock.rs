@@ -0,0 +1,373 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use slab::Slab;
+use std::fmt;
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard};
+
+use super::waiter::Waiter;
+
+/// A futures-aware reader-writer lock.
+///
+/// This allows any number of readers, or a single writer, to hold the lock
+/// at a time. The lock prefers writers: once a writer is waiting for the
+/// lock, new readers wait until it has been served, so that a steady stream
+/// of readers cannot starve writers.
+pub struct RwLock<T: ?Sized> {
+    state: StdMutex<State>,
+    value: UnsafeCell<T>,
+}
+
+struct State {
+    // Number of read guards alive
+    readers: usize,
+    // Whether a write guard is alive
+    writer: bool,
+    read_waiters: Slab<Waiter>,
+    write_waiters: Slab<Waiter>,
+}
+
+impl<T: ?Sized> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("RwLock")
+            .field("readers", &state.readers)
+            .field("is_write_locked", &state.writer)
+            .field("has_waiters", &!(state.read_waiters.is_empty() && state.write_waiters.is_empty()))
+            .finish()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(Default::default())
+    }
+}
+
+impl State {
+    fn can_read(&self) -> bool {
+        !self.writer && self.write_waiters.is_empty()
+    }
+
+    fn can_write(&self) -> bool {
+        !self.writer && self.readers == 0
+    }
+
+    // Wakes whoever can make progress now: a waiting writer if there is
+    // one, else all waiting readers.
+    fn wake_next(&mut self) {
+        if self.writer {
+            return;
+        }
+        if !self.write_waiters.is_empty() {
+            if self.readers == 0 {
+                if let Some((_i, waiter)) = self.write_waiters.iter_mut().next() {
+                    waiter.wake();
+                }
+            }
+        } else {
+            for (_i, waiter) in self.read_waiters.iter_mut() {
+                waiter.wake();
+            }
+        }
+    }
+}
+
+impl<T> RwLock<T> {
+    /// Creates a new futures-aware reader-writer lock.
+    pub fn new(t: T) -> RwLock<T> {
+        RwLock {
+            state: StdMutex::new(State {
+                readers: 0,
+                writer: false,
+                read_waiters: Slab::new(),
+                write_waiters: Slab::new(),
+            }),
+            value: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes this lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    fn lock_state(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Attempt to acquire the lock for reading immediately.
+    ///
+    /// If the lock is held by a writer, or a writer is waiting for it, this
+    /// will return `None`.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let mut state = self.lock_state();
+        if state.can_read() {
+            state.readers += 1;
+            Some(RwLockReadGuard { rwlock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Attempt to acquire the lock for writing immediately.
+    ///
+    /// If the lock is currently held, this will return `None`.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        let mut state = self.lock_state();
+        if state.can_write() {
+            state.writer = true;
+            Some(RwLockWriteGuard { rwlock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Acquire the lock for reading asynchronously.
+    ///
+    /// This method returns a future that will resolve once the lock has been
+    /// successfully acquired for reading.
+    pub fn read(&self) -> RwLockReadFuture<'_, T> {
+        RwLockReadFuture {
+            rwlock: Some(self),
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+
+    /// Acquire the lock for writing asynchronously.
+    ///
+    /// This method returns a future that will resolve once the lock has been
+    /// successfully acquired for writing.
+    pub fn write(&self) -> RwLockWriteFuture<'_, T> {
+        RwLockWriteFuture {
+            rwlock: Some(self),
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the lock mutably, no actual locking needs to
+    /// take place.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value.get() }
+    }
+}
+
+// Sentinel for when no slot in the `Slab` has been dedicated to this object.
+const WAIT_KEY_NONE: usize = usize::MAX;
+
+/// A future which resolves when the target lock has been successfully
+/// acquired for reading.
+pub struct RwLockReadFuture<'a, T: ?Sized> {
+    // `None` indicates that the lock was successfully acquired.
+    rwlock: Option<&'a RwLock<T>>,
+    wait_key: usize,
+}
+
+/// A future which resolves when the target lock has been successfully
+/// acquired for writing.
+pub struct RwLockWriteFuture<'a, T: ?Sized> {
+    // `None` indicates that the lock was successfully acquired.
+    rwlock: Option<&'a RwLock<T>>,
+    wait_key: usize,
+}
+
+impl<T: ?Sized> fmt::Debug for RwLockReadFuture<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLockReadFuture")
+            .field("was_acquired", &self.rwlock.is_none())
+            .field("rwlock", &self.rwlock)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for RwLockWriteFuture<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLockWriteFuture")
+            .field("was_acquired", &self.rwlock.is_none())
+            .field("rwlock", &self.rwlock)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> FusedFuture for RwLockReadFuture<'_, T> {
+    fn is_terminated(&self) -> bool {
+        self.rwlock.is_none()
+    }
+}
+
+impl<T: ?Sized> FusedFuture for RwLockWriteFuture<'_, T> {
+    fn is_terminated(&self) -> bool {
+        self.rwlock.is_none()
+    }
+}
+
+impl<'a, T: ?Sized> Future for RwLockReadFuture<'a, T> {
+    type Output = RwLockReadGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let rwlock = self.rwlock.expect("polled RwLockReadFuture after completion");
+        let mut state = rwlock.lock_state();
+
+        if state.can_read() {
+            state.readers += 1;
+            if self.wait_key != WAIT_KEY_NONE {
+                state.read_waiters.remove(self.wait_key);
+            }
+            self.rwlock = None;
+            return Poll::Ready(RwLockReadGuard { rwlock });
+        }
+
+        if self.wait_key == WAIT_KEY_NONE {
+            self.wait_key = state.read_waiters.insert(Waiter::Waiting(cx.waker().clone()));
+        } else {
+            state.read_waiters[self.wait_key].register(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl<'a, T: ?Sized> Future for RwLockWriteFuture<'a, T> {
+    type Output = RwLockWriteGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let rwlock = self.rwlock.expect("polled RwLockWriteFuture after completion");
+        let mut state = rwlock.lock_state();
+
+        if state.can_write() {
+            state.writer = true;
+            if self.wait_key != WAIT_KEY_NONE {
+                state.write_waiters.remove(self.wait_key);
+            }
+            self.rwlock = None;
+            return Poll::Ready(RwLockWriteGuard { rwlock });
+        }
+
+        if self.wait_key == WAIT_KEY_NONE {
+            self.wait_key = state.write_waiters.insert(Waiter::Waiting(cx.waker().clone()));
+        } else {
+            state.write_waiters[self.wait_key].register(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(rwlock) = self.rwlock {
+            if self.wait_key != WAIT_KEY_NONE {
+                rwlock.lock_state().read_waiters.remove(self.wait_key);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(rwlock) = self.rwlock {
+            if self.wait_key != WAIT_KEY_NONE {
+                // This future was dropped before it acquired the lock. It may
+                // have been woken to acquire it, or readers may be waiting
+                // only because of it, so pass the lock on.
+                let mut state = rwlock.lock_state();
+                state.write_waiters.remove(self.wait_key);
+                state.wake_next();
+            }
+        }
+    }
+}
+
+/// An RAII guard returned by the `read` and `try_read` methods.
+/// When this structure is dropped (falls out of scope), the read lock will
+/// be released.
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    rwlock: &'a RwLock<T>,
+}
+
+/// An RAII guard returned by the `write` and `try_write` methods.
+/// When this structure is dropped (falls out of scope), the write lock will
+/// be released.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    rwlock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLockReadGuard")
+            .field("value", &&**self)
+            .field("rwlock", &self.rwlock)
+            .finish()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLockWriteGuard")
+            .field("value", &&**self)
+            .field("rwlock", &self.rwlock)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.rwlock.lock_state();
+        state.readers -= 1;
+        state.wake_next();
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.rwlock.lock_state();
+        state.writer = false;
+        state.wake_next();
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.rwlock.value.get() }
+    }
+}
+
+// The lock can be moved between threads as long as the value can, and
+// shared between threads as long as the value can be both sent (to a
+// writer) and shared (between readers).
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+// It's safe to switch which thread the acquire is being attempted on so long
+// as the resulting guard could be sent there.
+unsafe impl<T: ?Sized + Sync> Send for RwLockReadFuture<'_, T> {}
+unsafe impl<T: ?Sized + Send + Sync> Send for RwLockWriteFuture<'_, T> {}
+// doesn't have any interesting `&self` methods (only Debug)
+unsafe impl<T: ?Sized> Sync for RwLockReadFuture<'_, T> {}
+unsafe impl<T: ?Sized> Sync for RwLockWriteFuture<'_, T> {}
+
+// A read guard only hands out `&T`, so it can be sent to and shared with
+// other threads when `T: Sync`. A write guard also hands out `&mut T`, so
+// sending it requires `T: Send` too.
+unsafe impl<T: ?Sized + Sync> Send for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Send + Sync> Send for RwLockWriteGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
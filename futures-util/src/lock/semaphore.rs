@@ -0,0 +1,455 @@
+use futures_core::future::{FusedFuture, Future};
+use futures_core::task::{Context, Poll};
+use slab::Slab;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard};
+
+use super::waiter::Waiter;
+
+/// A futures-aware counting semaphore.
+///
+/// A semaphore holds a number of permits, which tasks acquire and release
+/// to limit how many of them can do something at the same time. Tasks
+/// waiting to acquire permits are served in the order they started waiting:
+/// a task waiting for many permits holds up later tasks, even those which
+/// would only need a few.
+///
+/// Permits are returned to the semaphore when the [`SemaphorePermit`] or
+/// [`OwnedSemaphorePermit`] guard holding them is dropped.
+pub struct Semaphore {
+    state: StdMutex<State>,
+}
+
+struct State {
+    permits: usize,
+    closed: bool,
+    waiters: Slab<Acquirer>,
+    // Keys into `waiters` which haven't been granted their permits yet, in
+    // the order they started waiting
+    queue: VecDeque<usize>,
+}
+
+struct Acquirer {
+    waiter: Waiter,
+    permits: usize,
+    // Set once this acquirer has been handed its permits
+    granted: bool,
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock_state();
+        f.debug_struct("Semaphore")
+            .field("permits", &state.permits)
+            .field("is_closed", &state.closed)
+            .field("has_waiters", &!state.queue.is_empty())
+            .finish()
+    }
+}
+
+/// The error returned by a semaphore's acquire futures when the semaphore
+/// was closed before the permits could be acquired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AcquireError;
+
+/// The error returned by [`Semaphore::try_acquire`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryAcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// There are not enough permits available, or other tasks are already
+    /// waiting for them.
+    NoPermits,
+}
+
+impl State {
+    // Hands permits out to waiting acquirers in order, for as long as there
+    // are enough for the one at the front of the queue.
+    fn grant_waiters(&mut self) {
+        while let Some(&key) = self.queue.front() {
+            let n = self.waiters[key].permits;
+            if n > self.permits {
+                break;
+            }
+            self.permits -= n;
+            self.queue.pop_front();
+            let acquirer = &mut self.waiters[key];
+            acquirer.granted = true;
+            acquirer.waiter.wake();
+        }
+    }
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with `permits` permits available.
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: StdMutex::new(State {
+                permits,
+                closed: false,
+                waiters: Slab::new(),
+                queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn lock_state(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Returns the number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.lock_state().permits
+    }
+
+    /// Adds `n` permits to the semaphore, waking tasks waiting to acquire
+    /// them.
+    pub fn add_permits(&self, n: usize) {
+        self.release(n, WAIT_KEY_NONE);
+    }
+
+    /// Closes the semaphore.
+    ///
+    /// All tasks waiting to acquire permits, and any which try to acquire
+    /// permits later, fail with an error. Permits which have already been
+    /// acquired are unaffected.
+    pub fn close(&self) {
+        let mut state = self.lock_state();
+        state.closed = true;
+        let queue = state.queue.split_off(0);
+        for key in queue {
+            state.waiters[key].waiter.wake();
+        }
+    }
+
+    /// Returns whether the semaphore has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.lock_state().closed
+    }
+
+    /// Attempt to acquire `n` permits immediately.
+    ///
+    /// This fails if fewer than `n` permits are available, and also if other
+    /// tasks are already waiting to acquire permits, so that they don't get
+    /// overtaken.
+    pub fn try_acquire(&self, n: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_permits(n)?;
+        Ok(SemaphorePermit { semaphore: self, permits: n })
+    }
+
+    /// Attempt to acquire `n` permits immediately, returning a guard which
+    /// keeps the semaphore alive.
+    ///
+    /// This behaves like [`try_acquire`](Semaphore::try_acquire), but the
+    /// returned permit isn't tied to the lifetime of a borrow.
+    pub fn try_acquire_owned(
+        self: &Arc<Self>,
+        n: usize,
+    ) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+        self.try_acquire_permits(n)?;
+        Ok(OwnedSemaphorePermit { semaphore: self.clone(), permits: n })
+    }
+
+    /// Acquire `n` permits asynchronously.
+    ///
+    /// This method returns a future that will resolve once the permits have
+    /// been acquired, or with an error if the semaphore is closed first.
+    /// A future asking for more permits than the semaphore will ever have
+    /// never resolves, and holds up all the tasks waiting after it.
+    pub fn acquire(&self, n: usize) -> SemaphoreAcquireFuture<'_> {
+        SemaphoreAcquireFuture {
+            semaphore: Some(self),
+            permits: n,
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+
+    /// Acquire `n` permits asynchronously, resolving to a guard which keeps
+    /// the semaphore alive.
+    ///
+    /// This behaves like [`acquire`](Semaphore::acquire), but neither the
+    /// future nor the permit is tied to the lifetime of a borrow.
+    pub fn acquire_owned(self: &Arc<Self>, n: usize) -> SemaphoreAcquireOwnedFuture {
+        SemaphoreAcquireOwnedFuture {
+            semaphore: Some(self.clone()),
+            permits: n,
+            wait_key: WAIT_KEY_NONE,
+        }
+    }
+
+    fn try_acquire_permits(&self, n: usize) -> Result<(), TryAcquireError> {
+        let mut state = self.lock_state();
+        if state.closed {
+            Err(TryAcquireError::Closed)
+        } else if state.queue.is_empty() && state.permits >= n {
+            state.permits -= n;
+            Ok(())
+        } else {
+            Err(TryAcquireError::NoPermits)
+        }
+    }
+
+    fn poll_acquire(
+        &self,
+        n: usize,
+        wait_key: &mut usize,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), AcquireError>> {
+        let mut state = self.lock_state();
+
+        if *wait_key != WAIT_KEY_NONE {
+            if state.waiters[*wait_key].granted {
+                state.waiters.remove(*wait_key);
+                *wait_key = WAIT_KEY_NONE;
+                return Poll::Ready(Ok(()));
+            }
+            if state.closed {
+                state.waiters.remove(*wait_key);
+                *wait_key = WAIT_KEY_NONE;
+                return Poll::Ready(Err(AcquireError));
+            }
+            state.waiters[*wait_key].waiter.register(cx.waker());
+            return Poll::Pending;
+        }
+
+        if state.closed {
+            return Poll::Ready(Err(AcquireError));
+        }
+        if state.queue.is_empty() && state.permits >= n {
+            state.permits -= n;
+            return Poll::Ready(Ok(()));
+        }
+        *wait_key = state.waiters.insert(Acquirer {
+            waiter: Waiter::Waiting(cx.waker().clone()),
+            permits: n,
+            granted: false,
+        });
+        let key = *wait_key;
+        state.queue.push_back(key);
+        Poll::Pending
+    }
+
+    // Gives back `n` permits, and forgets about the acquirer at `wait_key`
+    // if there is one.
+    fn release(&self, mut n: usize, wait_key: usize) {
+        let mut state = self.lock_state();
+        if wait_key != WAIT_KEY_NONE {
+            let acquirer = state.waiters.remove(wait_key);
+            if !acquirer.granted {
+                // Not granted yet, so there are no permits to give back
+                n = 0;
+                state.queue.retain(|&key| key != wait_key);
+            }
+        }
+        state.permits = state.permits.checked_add(n)
+            .expect("semaphore permit count overflowed");
+        if !state.closed {
+            state.grant_waiters();
+        }
+    }
+}
+
+// Sentinel for when no slot in the `Slab` has been dedicated to this object.
+const WAIT_KEY_NONE: usize = usize::MAX;
+
+/// A future which resolves when the requested permits have been acquired
+/// from the semaphore.
+pub struct SemaphoreAcquireFuture<'a> {
+    // `None` indicates that the permits were successfully acquired.
+    semaphore: Option<&'a Semaphore>,
+    permits: usize,
+    wait_key: usize,
+}
+
+/// A future which resolves when the requested permits have been acquired
+/// from the semaphore, holding on to the semaphore through an `Arc`.
+pub struct SemaphoreAcquireOwnedFuture {
+    // `None` indicates that the permits were successfully acquired.
+    semaphore: Option<Arc<Semaphore>>,
+    permits: usize,
+    wait_key: usize,
+}
+
+impl fmt::Debug for SemaphoreAcquireFuture<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphoreAcquireFuture")
+            .field("was_acquired", &self.semaphore.is_none())
+            .field("permits", &self.permits)
+            .field("semaphore", &self.semaphore)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SemaphoreAcquireOwnedFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphoreAcquireOwnedFuture")
+            .field("was_acquired", &self.semaphore.is_none())
+            .field("permits", &self.permits)
+            .field("semaphore", &self.semaphore)
+            .finish()
+    }
+}
+
+impl FusedFuture for SemaphoreAcquireFuture<'_> {
+    fn is_terminated(&self) -> bool {
+        self.semaphore.is_none()
+    }
+}
+
+impl FusedFuture for SemaphoreAcquireOwnedFuture {
+    fn is_terminated(&self) -> bool {
+        self.semaphore.is_none()
+    }
+}
+
+impl<'a> Future for SemaphoreAcquireFuture<'a> {
+    type Output = Result<SemaphorePermit<'a>, AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let semaphore = this.semaphore.expect("polled SemaphoreAcquireFuture after completion");
+        match semaphore.poll_acquire(this.permits, &mut this.wait_key, cx) {
+            Poll::Ready(res) => {
+                this.semaphore = None;
+                Poll::Ready(res.map(|()| SemaphorePermit { semaphore, permits: this.permits }))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Future for SemaphoreAcquireOwnedFuture {
+    type Output = Result<OwnedSemaphorePermit, AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let res = {
+            let semaphore = this.semaphore.as_ref()
+                .expect("polled SemaphoreAcquireOwnedFuture after completion");
+            match semaphore.poll_acquire(this.permits, &mut this.wait_key, cx) {
+                Poll::Ready(res) => res,
+                Poll::Pending => return Poll::Pending,
+            }
+        };
+        let semaphore = this.semaphore.take().unwrap();
+        Poll::Ready(res.map(|()| OwnedSemaphorePermit { semaphore, permits: this.permits }))
+    }
+}
+
+impl Drop for SemaphoreAcquireFuture<'_> {
+    fn drop(&mut self) {
+        if let Some(semaphore) = self.semaphore {
+            if self.wait_key != WAIT_KEY_NONE {
+                // Hand back the permits if we were granted them, and let the
+                // acquirers queued behind us go ahead either way.
+                semaphore.release(self.permits, self.wait_key);
+            }
+        }
+    }
+}
+
+impl Drop for SemaphoreAcquireOwnedFuture {
+    fn drop(&mut self) {
+        if let Some(semaphore) = &self.semaphore {
+            if self.wait_key != WAIT_KEY_NONE {
+                semaphore.release(self.permits, self.wait_key);
+            }
+        }
+    }
+}
+
+/// An RAII guard holding permits acquired from a [`Semaphore`]. When this
+/// structure is dropped (falls out of scope), the permits are returned to
+/// the semaphore.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+/// An RAII guard holding permits acquired from a [`Semaphore`] through an
+/// `Arc`. When this structure is dropped (falls out of scope), the permits
+/// are returned to the semaphore.
+pub struct OwnedSemaphorePermit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl fmt::Debug for SemaphorePermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphorePermit")
+            .field("permits", &self.permits)
+            .field("semaphore", &self.semaphore)
+            .finish()
+    }
+}
+
+impl fmt::Debug for OwnedSemaphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedSemaphorePermit")
+            .field("permits", &self.permits)
+            .field("semaphore", &self.semaphore)
+            .finish()
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// Returns the number of permits held by this guard.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permits without returning them to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl OwnedSemaphorePermit {
+    /// Returns the number of permits held by this guard.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permits without returning them to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "semaphore closed")
+    }
+}
+
+impl Error for AcquireError {}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => write!(f, "semaphore closed"),
+            TryAcquireError::NoPermits => write!(f, "no permits available"),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
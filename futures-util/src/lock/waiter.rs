@@ -0,0 +1,24 @@
+use futures_core::task::Waker;
+use std::mem;
+
+/// A task waiting for a lock, as stored in the lock's list of waiters.
+pub(super) enum Waiter {
+    Waiting(Waker),
+    Woken,
+}
+
+impl Waiter {
+    pub(super) fn register(&mut self, waker: &Waker) {
+        match self {
+            Waiter::Waiting(w) if waker.will_wake(w) => {},
+            _ => *self = Waiter::Waiting(waker.clone()),
+        }
+    }
+
+    pub(super) fn wake(&mut self) {
+        match mem::replace(self, Waiter::Woken) {
+            Waiter::Waiting(waker) => waker.wake(),
+            Waiter::Woken => {},
+        }
+    }
+}
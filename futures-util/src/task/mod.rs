@@ -35,6 +35,19 @@ pub use self::noop_waker::noop_waker_ref;
 
 mod spawn;
 pub use self::spawn::{SpawnExt, LocalSpawnExt};
+#[cfg(feature = "alloc")]
+pub use self::spawn::TaskBuilder;
+
+#[cfg(feature = "std")]
+mod task_id;
+#[cfg(feature = "std")]
+pub use self::task_id::TaskId;
+
+#[cfg(feature = "std")]
+#[macro_use]
+mod task_local;
+#[cfg(feature = "std")]
+pub use self::task_local::{AccessError, TaskLocalFuture, TaskLocalKey};
 
 // re-export for `select!`
 #[doc(hidden)]
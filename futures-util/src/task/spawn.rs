@@ -7,9 +7,11 @@ use crate::future::{FutureExt, RemoteHandle};
 #[cfg(feature = "alloc")]
 use futures_core::future::{Future, FutureObj, LocalFutureObj};
 #[cfg(feature = "alloc")]
-use futures_core::task::SpawnError;
+use futures_core::task::{SpawnError, TaskOptions};
 #[cfg(feature = "alloc")]
 use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 
 impl<Sp: ?Sized> SpawnExt for Sp where Sp: Spawn {}
 impl<Sp: ?Sized> LocalSpawnExt for Sp where Sp: LocalSpawn {}
@@ -82,6 +84,24 @@ pub trait SpawnExt: Spawn {
         Ok(handle)
     }
 
+    /// Starts building a task to spawn, which allows setting options such as
+    /// the task's name.
+    ///
+    /// ```
+    /// #![feature(async_await, futures_api)]
+    /// use futures::executor::ThreadPool;
+    /// use futures::task::SpawnExt;
+    ///
+    /// let mut executor = ThreadPool::new().unwrap();
+    ///
+    /// let future = async { /* ... */ };
+    /// executor.build_task().name("conn-42").spawn(future).unwrap();
+    /// ```
+    #[cfg(feature = "alloc")]
+    fn build_task(&mut self) -> TaskBuilder<'_, Self> {
+        TaskBuilder::new(self)
+    }
+
     /// Wraps a [`Spawn`] and makes it usable as a futures 0.1 `Executor`.
     /// Requires the `compat` feature to enable.
     #[cfg(feature = "compat")]
@@ -160,4 +180,71 @@ pub trait LocalSpawnExt: LocalSpawn {
         self.spawn_local(future)?;
         Ok(handle)
     }
+
+    /// Starts building a task to spawn, which allows setting options such as
+    /// the task's name.
+    ///
+    /// This is the same as [`SpawnExt::build_task`](SpawnExt::build_task),
+    /// for spawners which only implement `LocalSpawn`.
+    #[cfg(feature = "alloc")]
+    fn build_local_task(&mut self) -> TaskBuilder<'_, Self> {
+        TaskBuilder::new(self)
+    }
+}
+
+/// A builder for a task, allowing to set its options before spawning it.
+///
+/// Created by [`SpawnExt::build_task`](SpawnExt::build_task) and
+/// [`LocalSpawnExt::build_local_task`](LocalSpawnExt::build_local_task).
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+#[must_use = "tasks are not spawned until `spawn` or `spawn_local` is called"]
+pub struct TaskBuilder<'a, Sp: ?Sized> {
+    spawner: &'a mut Sp,
+    name: Option<String>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Sp: ?Sized> TaskBuilder<'a, Sp> {
+    fn new(spawner: &'a mut Sp) -> Self {
+        TaskBuilder { spawner, name: None }
+    }
+
+    /// Sets the name of the task.
+    ///
+    /// See [`TaskOptions::with_name`](futures_core::task::TaskOptions::with_name).
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Spawns a task that polls the given future with output `()` to
+    /// completion.
+    pub fn spawn<Fut>(self, future: Fut) -> Result<(), SpawnError>
+    where
+        Sp: Spawn,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let TaskBuilder { spawner, name } = self;
+        spawner.spawn_obj_with(FutureObj::new(Box::new(future)), &options(&name))
+    }
+
+    /// Spawns a task that polls the given future with output `()` to
+    /// completion, without requiring it to be `Send`.
+    pub fn spawn_local<Fut>(self, future: Fut) -> Result<(), SpawnError>
+    where
+        Sp: LocalSpawn,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let TaskBuilder { spawner, name } = self;
+        spawner.spawn_local_obj_with(LocalFutureObj::new(Box::new(future)), &options(&name))
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn options(name: &Option<String>) -> TaskOptions<'_> {
+    match *name {
+        Some(ref name) => TaskOptions::new().with_name(name),
+        None => TaskOptions::new(),
+    }
 }
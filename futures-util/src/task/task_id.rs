@@ -0,0 +1,84 @@
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+thread_local! {
+    // The task being polled on this thread, if its executor tracks task ids
+    static CURRENT: Cell<Option<TaskId>> = Cell::new(None);
+}
+
+/// An opaque identifier of a task, unique within the process.
+///
+/// Executors which support task identifiers, like the ones in
+/// `futures::executor`, give every spawned task a fresh id and make it
+/// available to the task through [`TaskId::current`](TaskId::current) while
+/// polling it.
+///
+/// ```
+/// #![feature(futures_api)]
+/// use futures::executor::LocalPool;
+/// use futures::future::lazy;
+/// use futures::task::{SpawnExt, TaskId};
+///
+/// let mut pool = LocalPool::new();
+/// let handle = pool.spawner().spawn_with_handle(lazy(|_| TaskId::current())).unwrap();
+/// assert!(pool.run_until(handle).is_some());
+/// assert!(TaskId::current().is_none());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(usize);
+
+impl TaskId {
+    /// Allocates a new, unique task id.
+    ///
+    /// This is meant for executors, which should call
+    /// [`enter`](TaskId::enter) with the id when polling the task.
+    pub fn next() -> TaskId {
+        static NEXT: AtomicUsize = AtomicUsize::new(1);
+        TaskId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the id of the task currently being polled on this thread, or
+    /// `None` if called outside of a task or if the task's executor does not
+    /// track task ids.
+    pub fn current() -> Option<TaskId> {
+        CURRENT.with(|current| current.get())
+    }
+
+    /// Runs `f` with this id as the [`current`](TaskId::current) one.
+    ///
+    /// This is meant for executors, which should wrap each poll of a task
+    /// with this method.
+    pub fn enter<F, R>(self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        // Restores the enclosing id, even if `f` panics.
+        struct Reset(Option<TaskId>);
+
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(self.0));
+            }
+        }
+
+        let _reset = Reset(CURRENT.with(|current| current.replace(Some(self))));
+        f()
+    }
+
+    /// Returns the numeric value of this id.
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Debug for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TaskId").field(&self.0).finish()
+    }
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
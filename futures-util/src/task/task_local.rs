@@ -0,0 +1,206 @@
+use futures_core::future::Future;
+use futures_core::task::{Context, Poll};
+use pin_utils::{unsafe_pinned, unsafe_unpinned};
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::ptr;
+use std::thread::LocalKey;
+
+/// Declares new task-local keys of type
+/// [`TaskLocalKey`](crate::task::TaskLocalKey).
+///
+/// The syntax mirrors the standard library's `thread_local!` macro, except
+/// that no initial value is given: a task-local only holds a value inside of
+/// a future wrapped with [`TaskLocalKey::scope`](crate::task::TaskLocalKey::scope).
+///
+/// ```
+/// #![feature(futures_api)]
+/// use futures::executor::block_on;
+/// use futures::future::lazy;
+///
+/// futures::task_local! {
+///     static REQUEST_ID: u64;
+/// }
+///
+/// let id = block_on(REQUEST_ID.scope(42, lazy(|_| REQUEST_ID.with(|id| *id))));
+/// assert_eq!(id, 42);
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __task_local_inner {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::TaskLocalKey<$t> = {
+            ::std::thread_local! {
+                static __KEY: ::std::cell::Cell<*const $t> =
+                    ::std::cell::Cell::new(::std::ptr::null());
+            }
+
+            $crate::task::TaskLocalKey { __inner: &__KEY }
+        };
+    };
+}
+
+/// A key for task-local data.
+///
+/// A task-local value is attached to a future with
+/// [`scope`](TaskLocalKey::scope), and can be accessed with
+/// [`with`](TaskLocalKey::with) from anywhere that future is polled, across
+/// any number of suspension points. The value travels with the future rather
+/// than with the thread polling it, so it works with every executor,
+/// including ones that move tasks between threads.
+///
+/// Keys are declared with the [`task_local!`](crate::task_local) macro.
+pub struct TaskLocalKey<T: 'static> {
+    #[doc(hidden)]
+    pub __inner: &'static LocalKey<Cell<*const T>>,
+}
+
+impl<T: 'static> TaskLocalKey<T> {
+    /// Sets the value of this task-local to `value` while `future` is being
+    /// polled.
+    ///
+    /// Scopes can be nested: while the inner future is polled, the inner
+    /// value shadows the value of any enclosing scope for the same key.
+    pub fn scope<Fut: Future>(&'static self, value: T, future: Fut) -> TaskLocalFuture<T, Fut> {
+        TaskLocalFuture {
+            key: self,
+            slot: Some(value),
+            future,
+        }
+    }
+
+    /// Acquires a reference to the value of this task-local.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a future returned by
+    /// [`scope`](TaskLocalKey::scope) for this key.
+    pub fn with<F, R>(&'static self, f: F) -> R
+        where F: FnOnce(&T) -> R
+    {
+        match self.try_with(f) {
+            Ok(res) => res,
+            Err(_) => panic!("cannot access a task-local value outside of its scope"),
+        }
+    }
+
+    /// Acquires a reference to the value of this task-local, returning an
+    /// error instead of panicking if it is not set.
+    ///
+    /// `f` may itself access this key again, or poll a nested
+    /// [`scope`](TaskLocalKey::scope) for it.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+        where F: FnOnce(&T) -> R
+    {
+        let value = self.__inner.with(Cell::get);
+        if value.is_null() {
+            return Err(AccessError { _a: () });
+        }
+        // The pointer is only set while the value lives on the stack of an
+        // enclosing `TaskLocalFuture::poll`, which outlives this call, and
+        // nested scopes never move it.
+        Ok(f(unsafe { &*value }))
+    }
+}
+
+impl<T: 'static> fmt::Debug for TaskLocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskLocalKey").finish()
+    }
+}
+
+/// A future which sets a task-local value while polling its inner future.
+///
+/// Created by [`TaskLocalKey::scope`](TaskLocalKey::scope).
+#[must_use = "futures do nothing unless polled"]
+pub struct TaskLocalFuture<T: 'static, Fut> {
+    key: &'static TaskLocalKey<T>,
+    slot: Option<T>,
+    future: Fut,
+}
+
+impl<T: 'static, Fut: Unpin> Unpin for TaskLocalFuture<T, Fut> {}
+
+impl<T: 'static, Fut> TaskLocalFuture<T, Fut> {
+    unsafe_pinned!(future: Fut);
+    unsafe_unpinned!(slot: Option<T>);
+}
+
+impl<T: 'static, Fut: Future> Future for TaskLocalFuture<T, Fut> {
+    type Output = Fut::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Restores the value of any enclosing scope, even if the inner future
+        // panics.
+        struct Reset<T: 'static> {
+            key: &'static LocalKey<Cell<*const T>>,
+            prev: *const T,
+        }
+
+        impl<T: 'static> Drop for Reset<T> {
+            fn drop(&mut self) {
+                let prev = self.prev;
+                self.key.with(|cell| cell.set(prev));
+            }
+        }
+
+        let key = self.key.__inner;
+        // The value is kept on the stack while it is set, so that no borrow
+        // of `self` can alias the references handed out by `try_with`
+        let slot = self.as_mut().slot().take();
+        let ptr = slot.as_ref().map_or(ptr::null(), |value| value as *const T);
+        let prev = key.with(|cell| cell.replace(ptr));
+        let reset = Reset { key, prev };
+        let res = self.as_mut().future().poll(cx);
+        drop(reset);
+        *self.as_mut().slot() = slot;
+        res
+    }
+}
+
+impl<T: 'static + fmt::Debug, Fut: fmt::Debug> fmt::Debug for TaskLocalFuture<T, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskLocalFuture")
+            .field("slot", &self.slot)
+            .field("future", &self.future)
+            .finish()
+    }
+}
+
+/// An error returned by [`TaskLocalKey::try_with`](TaskLocalKey::try_with)
+/// when called outside of the task-local's scope.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AccessError {
+    _a: (),
+}
+
+impl fmt::Debug for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessError").finish()
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task-local value not set")
+    }
+}
+
+impl Error for AccessError {}
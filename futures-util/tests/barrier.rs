@@ -0,0 +1,60 @@
+#![feature(futures_api)]
+
+use futures::executor::block_on;
+use futures::future::FutureExt;
+use futures::lock::Barrier;
+use futures::task::{Context, Poll};
+use futures_test::task::{panic_waker_ref, new_count_waker};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn barrier_releases_group() {
+    let barrier = Barrier::new(3);
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut a = barrier.wait();
+    let mut b = barrier.wait();
+    assert!(a.poll_unpin(&mut cx).is_pending());
+    assert!(b.poll_unpin(&mut cx).is_pending());
+
+    match barrier.wait().poll_unpin(&mut Context::from_waker(panic_waker_ref())) {
+        Poll::Ready(res) => assert!(res.is_leader()),
+        Poll::Pending => panic!("expected the barrier to release"),
+    }
+    assert_eq!(counter, 2);
+    match (a.poll_unpin(&mut cx), b.poll_unpin(&mut cx)) {
+        (Poll::Ready(a), Poll::Ready(b)) => assert!(!a.is_leader() && !b.is_leader()),
+        _ => panic!("expected the barrier to release"),
+    }
+
+    // The barrier can be reused
+    assert!(barrier.wait().poll_unpin(&mut cx).is_pending());
+}
+
+#[test]
+fn barrier_dropped_waiter_leaves() {
+    let barrier = Barrier::new(2);
+    let (waker, _counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut a = barrier.wait();
+    assert!(a.poll_unpin(&mut cx).is_pending());
+    drop(a);
+
+    let mut b = barrier.wait();
+    assert!(b.poll_unpin(&mut cx).is_pending());
+}
+
+#[test]
+fn barrier_threads() {
+    let n = 8;
+    let barrier = Arc::new(Barrier::new(n));
+    let handles: Vec<_> = (0..n).map(|_| {
+        let barrier = barrier.clone();
+        thread::spawn(move || block_on(barrier.wait()).is_leader())
+    }).collect();
+    let leaders = handles.into_iter().map(|h| h.join().unwrap()).filter(|&l| l).count();
+    assert_eq!(leaders, 1);
+}
@@ -0,0 +1,80 @@
+#![feature(futures_api)]
+
+use futures::executor::block_on;
+use futures::future::FutureExt;
+use futures::lock::{Condvar, Mutex};
+use futures::task::{Context, Poll};
+use futures_test::task::new_count_waker;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn condvar_unlocks_while_waiting() {
+    let mutex = Mutex::new(false);
+    let condvar = Condvar::new();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut wait = condvar.wait(mutex.try_lock().unwrap());
+    assert!(wait.poll_unpin(&mut cx).is_pending());
+
+    *mutex.try_lock().unwrap() = true;
+    condvar.notify_one();
+    assert_eq!(counter, 1);
+
+    let guard = mutex.try_lock().unwrap();
+    assert!(wait.poll_unpin(&mut cx).is_pending());
+    drop(guard);
+    assert_eq!(counter, 2);
+    match wait.poll_unpin(&mut cx) {
+        Poll::Ready(guard) => assert!(*guard),
+        Poll::Pending => panic!("expected to lock the mutex again"),
+    };
+}
+
+#[test]
+fn condvar_notification_before_poll_is_not_missed() {
+    let mutex = Mutex::new(());
+    let condvar = Condvar::new();
+    let wait = condvar.wait(mutex.try_lock().unwrap());
+    condvar.notify_all();
+    block_on(wait);
+}
+
+#[test]
+fn condvar_notify_one_before_wait_is_not_stored() {
+    let mutex = Mutex::new(());
+    let condvar = Condvar::new();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    condvar.notify_one();
+    let mut wait = condvar.wait(mutex.try_lock().unwrap());
+    assert!(wait.poll_unpin(&mut cx).is_pending());
+    assert_eq!(counter, 0);
+
+    condvar.notify_one();
+    assert_eq!(counter, 1);
+    assert!(wait.poll_unpin(&mut cx).is_ready());
+}
+
+#[test]
+fn condvar_threads() {
+    let state = Arc::new((Mutex::new(0), Condvar::new()));
+    let n = 4;
+    let handles: Vec<_> = (0..n).map(|_| {
+        let state = state.clone();
+        thread::spawn(move || {
+            let (mutex, condvar) = &*state;
+            let mut guard = block_on(mutex.lock());
+            *guard += 1;
+            condvar.notify_all();
+            while *guard < n {
+                guard = block_on(condvar.wait(guard));
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
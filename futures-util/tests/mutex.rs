@@ -2,11 +2,11 @@
 
 use futures::channel::mpsc;
 use futures::future::{ready, FutureExt};
-use futures::lock::Mutex;
+use futures::lock::{Mutex, MutexGuard};
 use futures::stream::StreamExt;
-use futures::task::{Context, SpawnExt};
+use futures::task::{Context, Poll, SpawnExt};
 use futures_test::future::FutureTestExt;
-use futures_test::task::{panic_waker_ref, new_count_waker};
+use futures_test::task::{noop_waker_ref, panic_waker_ref, new_count_waker};
 use std::sync::Arc;
 
 #[test]
@@ -67,3 +67,94 @@ fn mutex_contested() {
         assert_eq!(num_tasks, *lock);
     })
 }
+
+#[test]
+fn mutex_fair_hands_off_in_order() {
+    let mutex = Mutex::new_fair(());
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let lock = mutex.try_lock().unwrap();
+
+    let mut first = mutex.lock();
+    let mut second = mutex.lock();
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+
+    drop(lock);
+
+    // The lock went to the oldest waiter and can't be taken from under it
+    assert_eq!(counter, 1);
+    assert!(mutex.try_lock().is_none());
+    assert!(mutex.lock().poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+    let lock = first.poll_unpin(&mut Context::from_waker(panic_waker_ref()));
+    assert!(lock.is_ready());
+    drop(lock);
+
+    assert_eq!(counter, 2);
+    assert!(second.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+}
+
+#[test]
+fn mutex_fair_dropped_waiter_passes_lock_on() {
+    let mutex = Mutex::new_fair(());
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let lock = mutex.try_lock().unwrap();
+
+    let mut first = mutex.lock();
+    let mut second = mutex.lock();
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+
+    drop(lock);
+    assert_eq!(counter, 1);
+    drop(first);
+    assert_eq!(counter, 2);
+    assert!(second.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+    assert!(mutex.try_lock().is_some());
+}
+
+#[test]
+fn mutex_fair_debug_reports_waiters() {
+    let mutex = Mutex::new_fair(());
+    let lock = mutex.try_lock().unwrap();
+    assert!(format!("{:?}", mutex).contains("has_waiters: false"));
+
+    let mut waiter = mutex.lock();
+    assert!(waiter.poll_unpin(&mut Context::from_waker(noop_waker_ref())).is_pending());
+    assert!(format!("{:?}", mutex).contains("has_waiters: true"));
+
+    drop(lock);
+    drop(waiter);
+    assert!(format!("{:?}", mutex).contains("has_waiters: false"));
+}
+
+#[test]
+fn mutex_lock_owned() {
+    let mutex = Arc::new(Mutex::new(0));
+    let mut lock = mutex.try_lock_owned().unwrap();
+    *lock += 1;
+
+    let (waker, counter) = new_count_waker();
+    let mut waiter = mutex.lock_owned();
+    assert!(waiter.poll_unpin(&mut Context::from_waker(&waker)).is_pending());
+
+    std::thread::spawn(move || drop(lock)).join().unwrap();
+    assert_eq!(counter, 1);
+
+    match waiter.poll_unpin(&mut Context::from_waker(panic_waker_ref())) {
+        Poll::Ready(lock) => assert_eq!(*lock, 1),
+        Poll::Pending => panic!("expected to acquire the lock"),
+    }
+}
+
+#[test]
+fn mutex_guard_map() {
+    let mutex = Mutex::new((0, vec![1]));
+    let mut items = MutexGuard::map(mutex.try_lock().unwrap(), |pair| &mut pair.1);
+    items.push(2);
+    assert!(mutex.try_lock().is_none());
+    drop(items);
+    assert_eq!(mutex.try_lock().unwrap().1, [1, 2]);
+}
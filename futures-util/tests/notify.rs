@@ -0,0 +1,69 @@
+#![feature(futures_api)]
+
+use futures::executor::block_on;
+use futures::future::FutureExt;
+use futures::lock::Notify;
+use futures::task::Context;
+use futures_test::task::{panic_waker_ref, new_count_waker};
+
+#[test]
+fn notify_one_stores_permit() {
+    let notify = Notify::new();
+    notify.notify_one();
+    notify.notify_one();
+    block_on(notify.notified());
+    assert!(notify.notified().poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_pending());
+}
+
+#[test]
+fn notify_one_wakes_oldest() {
+    let notify = Notify::new();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut first = notify.notified();
+    let mut second = notify.notified();
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+
+    notify.notify_one();
+    assert_eq!(counter, 1);
+    assert!(second.poll_unpin(&mut cx).is_pending());
+    assert!(first.poll_unpin(&mut cx).is_ready());
+}
+
+#[test]
+fn notify_one_passed_on_when_dropped() {
+    let notify = Notify::new();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut first = notify.notified();
+    let mut second = notify.notified();
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+
+    notify.notify_one();
+    drop(first);
+    assert_eq!(counter, 2);
+    assert!(second.poll_unpin(&mut cx).is_ready());
+}
+
+#[test]
+fn notify_waiters_wakes_all() {
+    let notify = Notify::new();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut polled = notify.notified();
+    let mut unpolled = notify.notified();
+    assert!(polled.poll_unpin(&mut cx).is_pending());
+
+    notify.notify_waiters();
+    assert_eq!(counter, 1);
+    assert!(polled.poll_unpin(&mut cx).is_ready());
+    assert!(unpolled.poll_unpin(&mut cx).is_ready());
+
+    // Not stored for later waiters
+    assert!(notify.notified().poll_unpin(&mut cx).is_pending());
+}
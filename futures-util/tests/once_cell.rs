@@ -0,0 +1,110 @@
+#![feature(futures_api)]
+
+use futures::channel::oneshot;
+use futures::executor::block_on;
+use futures::future::{ready, FutureExt};
+use futures::lock::OnceCell;
+use futures::task::{Context, Poll};
+use futures_test::task::{panic_waker_ref, new_count_waker};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[test]
+fn once_cell_get_or_init() {
+    let cell = OnceCell::new();
+    assert_eq!(cell.get(), None);
+    assert_eq!(*block_on(cell.get_or_init(|| ready(1))), 1);
+    assert_eq!(*block_on(cell.get_or_init(|| -> futures::future::Ready<i32> { panic!() })), 1);
+    assert_eq!(cell.get(), Some(&1));
+    assert_eq!(cell.set(2), Err(2));
+    assert_eq!(cell.into_inner(), Some(1));
+}
+
+#[test]
+fn once_cell_set() {
+    let cell = OnceCell::new();
+    assert_eq!(cell.set(1), Ok(()));
+    assert_eq!(cell.get(), Some(&1));
+    assert_eq!(*block_on(cell.get_or_init(|| ready(2))), 1);
+}
+
+#[test]
+fn once_cell_waits_for_initializer() {
+    let cell = OnceCell::new();
+    let (tx, rx) = oneshot::channel();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut first = cell.get_or_init(|| rx.map(Result::unwrap));
+    let mut second = cell.get_or_init(|| -> futures::future::Ready<i32> { panic!() });
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+    assert_eq!(cell.set(3), Err(3));
+
+    tx.send(1).unwrap();
+    assert!(first.poll_unpin(&mut cx).is_ready());
+    assert_eq!(counter, 2);
+    match second.poll_unpin(&mut Context::from_waker(panic_waker_ref())) {
+        Poll::Ready(value) => assert_eq!(*value, 1),
+        Poll::Pending => panic!("expected the cell to be initialized"),
+    }
+}
+
+#[test]
+fn once_cell_failed_initializer_lets_next_retry() {
+    let cell = OnceCell::new();
+    let (tx, rx) = oneshot::channel::<i32>();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut first = cell.get_or_try_init(|| rx);
+    let mut second = cell.get_or_try_init(|| ready(Ok::<_, oneshot::Canceled>(2)));
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+
+    drop(tx);
+    assert!(match first.poll_unpin(&mut cx) {
+        Poll::Ready(Err(oneshot::Canceled)) => true,
+        _ => false,
+    });
+    assert_eq!(cell.get(), None);
+    assert_eq!(counter, 2);
+    assert_eq!(second.poll_unpin(&mut cx), Poll::Ready(Ok(&2)));
+}
+
+#[test]
+fn once_cell_dropped_initializer_lets_next_retry() {
+    let cell = OnceCell::new();
+    let (_tx, rx) = oneshot::channel();
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut first = cell.get_or_init(|| rx.map(Result::unwrap));
+    let mut second = cell.get_or_init(|| ready(2));
+    assert!(first.poll_unpin(&mut cx).is_pending());
+    assert!(second.poll_unpin(&mut cx).is_pending());
+
+    drop(first);
+    assert_eq!(counter, 1);
+    assert_eq!(second.poll_unpin(&mut cx), Poll::Ready(&2));
+}
+
+#[test]
+fn once_cell_threads() {
+    let cell = Arc::new(OnceCell::new());
+    let calls = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..8).map(|i| {
+        let cell = cell.clone();
+        let calls = calls.clone();
+        thread::spawn(move || {
+            *block_on(cell.get_or_init(|| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                ready(i)
+            }))
+        })
+    }).collect();
+    let values: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(values.iter().all(|&v| v == values[0]));
+}
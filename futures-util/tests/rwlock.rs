@@ -0,0 +1,112 @@
+#![feature(futures_api)]
+
+use futures::future::FutureExt;
+use futures::lock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use futures::task::{Context, Poll};
+use futures_test::task::{panic_waker_ref, new_count_waker};
+
+#[test]
+fn rwlock_acquire_uncontested() {
+    let rwlock = RwLock::new(());
+    for _ in 0..10 {
+        assert!(rwlock.read().poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+        assert!(rwlock.write().poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+    }
+}
+
+#[test]
+fn rwlock_many_readers() {
+    let rwlock = RwLock::new(1);
+    let r1 = rwlock.try_read().unwrap();
+    let r2 = rwlock.try_read().unwrap();
+    assert_eq!(*r1 + *r2, 2);
+    assert!(rwlock.try_write().is_none());
+    drop(r1);
+    assert!(rwlock.try_write().is_none());
+    drop(r2);
+    assert!(rwlock.try_write().is_some());
+}
+
+#[test]
+fn rwlock_writer_excludes_all() {
+    let rwlock = RwLock::new(0);
+    let mut w = rwlock.try_write().unwrap();
+    *w += 1;
+    assert!(rwlock.try_read().is_none());
+    assert!(rwlock.try_write().is_none());
+    drop(w);
+    assert_eq!(*rwlock.try_read().unwrap(), 1);
+}
+
+#[test]
+fn rwlock_wakes_readers_after_writer() {
+    let rwlock = RwLock::new(());
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let w = rwlock.try_write().unwrap();
+
+    let mut r1 = rwlock.read();
+    let mut r2 = rwlock.read();
+    assert!(r1.poll_unpin(&mut cx).is_pending());
+    assert!(r2.poll_unpin(&mut cx).is_pending());
+    assert_eq!(counter, 0);
+
+    drop(w);
+
+    assert_eq!(counter, 2);
+    assert!(r1.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+    assert!(r2.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+}
+
+#[test]
+fn rwlock_prefers_writers() {
+    let rwlock = RwLock::new(());
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let r = rwlock.try_read().unwrap();
+
+    let mut writer = rwlock.write();
+    assert!(writer.poll_unpin(&mut cx).is_pending());
+
+    // A waiting writer blocks new readers
+    assert!(rwlock.try_read().is_none());
+    let mut reader = rwlock.read();
+    assert!(reader.poll_unpin(&mut cx).is_pending());
+
+    drop(r);
+    assert_eq!(counter, 1);
+    let w = match writer.poll_unpin(&mut Context::from_waker(panic_waker_ref())) {
+        Poll::Ready(w) => w,
+        Poll::Pending => panic!("writer should have acquired the lock"),
+    };
+    assert!(reader.poll_unpin(&mut cx).is_pending());
+
+    drop(w);
+    assert_eq!(counter, 2);
+    assert!(reader.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+}
+
+#[test]
+fn rwlock_dropped_writer_releases_readers() {
+    let rwlock = RwLock::new(());
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let r = rwlock.try_read().unwrap();
+
+    let mut writer = rwlock.write();
+    assert!(writer.poll_unpin(&mut cx).is_pending());
+    let mut reader = rwlock.read();
+    assert!(reader.poll_unpin(&mut cx).is_pending());
+
+    drop(writer);
+    assert_eq!(counter, 1);
+    assert!(reader.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+    drop(r);
+}
+
+#[test]
+fn rwlock_guards_are_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<RwLockReadGuard<'_, u32>>();
+    assert_send::<RwLockWriteGuard<'_, u32>>();
+}
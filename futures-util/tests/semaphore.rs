@@ -0,0 +1,144 @@
+#![feature(futures_api)]
+
+use futures::future::FutureExt;
+use futures::lock::{AcquireError, Semaphore, TryAcquireError};
+use futures::task::{Context, Poll};
+use futures_test::task::{panic_waker_ref, new_count_waker};
+use std::sync::Arc;
+
+#[test]
+fn semaphore_try_acquire() {
+    let sem = Semaphore::new(3);
+    let p1 = sem.try_acquire(2).unwrap();
+    assert_eq!(p1.num_permits(), 2);
+    assert_eq!(sem.available_permits(), 1);
+    assert_eq!(sem.try_acquire(2).unwrap_err(), TryAcquireError::NoPermits);
+    let p2 = sem.try_acquire(1).unwrap();
+    assert_eq!(sem.available_permits(), 0);
+    drop(p1);
+    drop(p2);
+    assert_eq!(sem.available_permits(), 3);
+}
+
+#[test]
+fn semaphore_acquire_uncontested() {
+    let sem = Semaphore::new(1);
+    for _ in 0..10 {
+        match sem.acquire(1).poll_unpin(&mut Context::from_waker(panic_waker_ref())) {
+            Poll::Ready(Ok(_)) => {}
+            _ => panic!("expected to acquire a permit"),
+        }
+    }
+}
+
+#[test]
+fn semaphore_wakes_waiters() {
+    let sem = Semaphore::new(1);
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let permit = sem.try_acquire(1).unwrap();
+
+    let mut waiter = sem.acquire(1);
+    assert!(waiter.poll_unpin(&mut cx).is_pending());
+    assert_eq!(counter, 0);
+
+    drop(permit);
+
+    assert_eq!(counter, 1);
+    assert_eq!(sem.available_permits(), 0);
+    assert!(waiter.poll_unpin(&mut Context::from_waker(panic_waker_ref())).is_ready());
+}
+
+#[test]
+fn semaphore_is_fifo() {
+    let sem = Semaphore::new(2);
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+    let permit = sem.try_acquire(2).unwrap();
+
+    let mut big = sem.acquire(2);
+    let mut small = sem.acquire(1);
+    assert!(big.poll_unpin(&mut cx).is_pending());
+    assert!(small.poll_unpin(&mut cx).is_pending());
+
+    // A later, smaller request doesn't overtake the one waiting before it
+    sem.add_permits(1);
+    assert_eq!(counter, 0);
+    assert!(small.poll_unpin(&mut cx).is_pending());
+    assert_eq!(sem.try_acquire(1).unwrap_err(), TryAcquireError::NoPermits);
+
+    drop(permit);
+    assert_eq!(counter, 2);
+    assert!(big.poll_unpin(&mut cx).is_ready());
+    assert!(small.poll_unpin(&mut cx).is_ready());
+}
+
+#[test]
+fn semaphore_dropped_waiter_lets_others_through() {
+    let sem = Semaphore::new(1);
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut big = sem.acquire(2);
+    let mut small = sem.acquire(1);
+    assert!(big.poll_unpin(&mut cx).is_pending());
+    assert!(small.poll_unpin(&mut cx).is_pending());
+
+    drop(big);
+    assert_eq!(counter, 1);
+    assert!(small.poll_unpin(&mut cx).is_ready());
+}
+
+#[test]
+fn semaphore_dropped_granted_waiter_returns_permits() {
+    let sem = Semaphore::new(0);
+    let (waker, _counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut waiter = sem.acquire(1);
+    assert!(waiter.poll_unpin(&mut cx).is_pending());
+    sem.add_permits(1);
+    assert_eq!(sem.available_permits(), 0);
+
+    drop(waiter);
+    assert_eq!(sem.available_permits(), 1);
+}
+
+#[test]
+fn semaphore_close_fails_waiters() {
+    let sem = Semaphore::new(0);
+    let (waker, counter) = new_count_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut waiter = sem.acquire(1);
+    assert!(waiter.poll_unpin(&mut cx).is_pending());
+
+    sem.close();
+    assert!(sem.is_closed());
+    assert_eq!(counter, 1);
+    match waiter.poll_unpin(&mut cx) {
+        Poll::Ready(Err(AcquireError)) => {}
+        _ => panic!("expected the acquire to fail"),
+    }
+    assert_eq!(sem.try_acquire(0).unwrap_err(), TryAcquireError::Closed);
+}
+
+#[test]
+fn semaphore_owned_permits() {
+    let sem = Arc::new(Semaphore::new(1));
+    let permit = sem.try_acquire_owned(1).unwrap();
+    let mut waiter = sem.acquire_owned(1);
+    let (waker, counter) = new_count_waker();
+    assert!(waiter.poll_unpin(&mut Context::from_waker(&waker)).is_pending());
+
+    let handle = std::thread::spawn(move || drop(permit));
+    handle.join().unwrap();
+    assert_eq!(counter, 1);
+
+    let permit = match waiter.poll_unpin(&mut Context::from_waker(panic_waker_ref())) {
+        Poll::Ready(Ok(permit)) => permit,
+        _ => panic!("expected to acquire a permit"),
+    };
+    permit.forget();
+    assert_eq!(sem.available_permits(), 0);
+}
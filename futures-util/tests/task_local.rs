@@ -0,0 +1,77 @@
+#![feature(futures_api)]
+
+use futures::channel::oneshot;
+use futures::executor::{block_on, ThreadPool};
+use futures::future::{lazy, FutureExt};
+use futures::task::Poll;
+
+futures::task_local! {
+    static NUMBER: u32;
+    static NAME: String;
+}
+
+#[test]
+fn task_local_outside_scope() {
+    assert!(NUMBER.try_with(|n| *n).is_err());
+}
+
+#[test]
+#[should_panic]
+fn task_local_with_outside_scope_panics() {
+    NUMBER.with(|_| ());
+}
+
+#[test]
+fn task_local_scope() {
+    let fut = NUMBER.scope(1, lazy(|_| NUMBER.with(|n| *n)));
+    assert_eq!(block_on(fut), 1);
+    assert!(NUMBER.try_with(|n| *n).is_err());
+}
+
+#[test]
+fn task_local_nested_scopes() {
+    let fut = NUMBER.scope(1, lazy(|_| {
+        NUMBER.scope(2, lazy(|_| NUMBER.with(|n| *n)))
+    }).flatten().map(|inner| (inner, NUMBER.with(|n| *n))));
+    assert_eq!(block_on(fut), (2, 1));
+}
+
+#[test]
+fn task_local_nested_scope_inside_with() {
+    let fut = NUMBER.scope(1, lazy(|cx| {
+        NUMBER.with(|outer| {
+            let mut inner = NUMBER.scope(2, lazy(|_| NUMBER.with(|n| *n)));
+            match inner.poll_unpin(cx) {
+                Poll::Ready(n) => (*outer, n, NUMBER.with(|n| *n)),
+                Poll::Pending => panic!("inner scope did not complete"),
+            }
+        })
+    }));
+    assert_eq!(block_on(fut), (1, 2, 1));
+}
+
+#[test]
+fn task_local_independent_keys() {
+    let fut = NAME.scope("conn".to_string(), NUMBER.scope(42, lazy(|_| {
+        NAME.with(|name| format!("{}-{}", name, NUMBER.with(|n| *n)))
+    })));
+    assert_eq!(block_on(fut), "conn-42");
+}
+
+#[test]
+fn task_local_across_suspension_on_thread_pool() {
+    let (tx, rx) = oneshot::channel::<u32>();
+    let mut pool = ThreadPool::builder().pool_size(2).create().unwrap();
+
+    let handle = pool.spawn_with_join_handle(NUMBER.scope(7, rx.map(|v| {
+        v.unwrap() + NUMBER.with(|n| *n)
+    }))).unwrap();
+
+    // the task is suspended on `rx` while another task polls outside of the
+    // scope on the same pool
+    let other = pool.spawn_with_join_handle(lazy(|_| NUMBER.try_with(|n| *n).is_err())).unwrap();
+    assert!(block_on(other).unwrap());
+
+    tx.send(1).unwrap();
+    assert_eq!(block_on(handle).unwrap(), 8);
+}
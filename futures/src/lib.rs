@@ -63,6 +63,8 @@ pub use futures_util::{
 pub use futures_util::{
     // Async-await
     join, try_join, pending, poll,
+    // Task-local storage
+    task_local,
 };
 
 #[cfg(feature = "std")]
@@ -77,8 +79,21 @@ pub mod channel {
     //! - [mpsc](crate::channel::mpsc), a multi-producer, single-consumer
     //!   channel for sending values between tasks, analogous to the
     //!   similarly-named structure in the standard library.
-
-    pub use futures_channel::{oneshot, mpsc};
+    //!
+    //! It also contains more specialized channels:
+    //!
+    //! - [mpmc](crate::channel::mpmc), a bounded channel which can have
+    //!   several receivers, each value being received by only one of them.
+    //! - [broadcast](crate::channel::broadcast), a channel where every value
+    //!   sent is received by every receiver.
+    //! - [watch](crate::channel::watch), a channel which only retains the
+    //!   most recently sent value.
+    //! - [lossy](crate::channel::lossy), a bounded channel which drops
+    //!   messages when full instead of making senders wait.
+    //! - [priority](crate::channel::priority), a bounded channel which
+    //!   delivers messages by priority.
+
+    pub use futures_channel::{oneshot, mpsc, mpmc, priority, broadcast, watch, lossy};
 }
 
 #[cfg(feature = "compat")]
@@ -165,8 +180,10 @@ pub mod executor {
     pub use futures_executor::{
         BlockingStream,
         Enter, EnterError,
+        JoinError, JoinHandle,
         LocalSpawner, LocalPool,
-        ThreadPool, ThreadPoolBuilder,
+        Metrics, PanicPolicy,
+        ShutdownPolicy, SpawnBlocking, ThreadPool, ThreadPoolBuilder,
         block_on, block_on_stream, enter,
     };
 }
@@ -273,7 +290,24 @@ pub mod io {
 #[cfg(feature = "std")]
 pub mod lock {
     //! Futures-powered synchronization primitives.
-    pub use futures_util::lock::{Mutex, MutexLockFuture, MutexGuard};
+    pub use futures_util::lock::{
+        MappedMutexGuard, Mutex, MutexLockFuture, MutexGuard, OwnedMutexGuard,
+        OwnedMutexLockFuture,
+    };
+    pub use futures_util::lock::{
+        RwLock, RwLockReadFuture, RwLockReadGuard, RwLockWriteFuture, RwLockWriteGuard,
+    };
+    pub use futures_util::lock::{
+        Barrier, BarrierWaitFuture, BarrierWaitResult, Condvar, CondvarWaitFuture, Notified,
+        Notify,
+    };
+    pub use futures_util::lock::{
+        OnceCell, OnceCellGetOrInitFuture, OnceCellGetOrTryInitFuture,
+    };
+    pub use futures_util::lock::{
+        AcquireError, OwnedSemaphorePermit, Semaphore, SemaphoreAcquireFuture,
+        SemaphoreAcquireOwnedFuture, SemaphorePermit, TryAcquireError,
+    };
 }
 
 pub mod prelude {
@@ -416,7 +450,7 @@ pub mod task {
     //! executors or dealing with synchronization issues around task wakeup.
 
     pub use futures_core::task::{
-        Context, Poll, Spawn, LocalSpawn, SpawnError,
+        Context, Poll, Spawn, LocalSpawn, SpawnError, TaskOptions,
         Waker, RawWaker, RawWakerVTable
     };
 
@@ -425,8 +459,11 @@ pub mod task {
     #[cfg(feature = "std")]
     pub use futures_util::task::noop_waker_ref;
 
+    #[cfg(feature = "std")]
+    pub use futures_util::task::{AccessError, TaskId, TaskLocalFuture, TaskLocalKey};
+
     #[cfg(feature = "alloc")]
-    pub use futures_util::task::{SpawnExt, LocalSpawnExt};
+    pub use futures_util::task::{SpawnExt, LocalSpawnExt, TaskBuilder};
 
     #[cfg_attr(
         feature = "cfg-target-has-atomic",